@@ -4,6 +4,7 @@
 ///
 use super::*;
 use crate::controllers::*;
+use crate::theme::AUTOSAVE_INTERVAL_FRAMES;
 
 use tweek::{
     core::*,
@@ -84,6 +85,8 @@ impl AppDelegate {
         // self.front_controller.set
         self.front_controller.view_will_load();
         self.front_controller.set_theme(&mut self.theme);
+        // A recovery journal left from the last session means it did not quit normally
+        self.front_controller.check_recovery();
     }
 
 }
@@ -151,6 +154,11 @@ impl State for AppDelegate {
         }
         self.front_controller.update(window, &mut self.app_state);
 
+        self.frames += 1;
+        if self.frames % AUTOSAVE_INTERVAL_FRAMES == 0 {
+            self.front_controller.autosave();
+        }
+
         // self.frames += 1;
         // if (self.frames % FPS_INTERVAL) == 0 {
         //     self.frames = 0;
@@ -190,16 +198,32 @@ impl State for AppDelegate {
             Event::MouseWheel(xy) => {
                 self.front_controller.handle_mouse_scroll(xy, &mut self.app_state);
             }
-            Event::Key(key, ButtonState::Pressed) => match key {
-                Key::Escape => {
-                    window.close();
+            // Keys go to the front controller first, so that eg: Escape can cancel text entry before it quits
+            Event::Key(key, ButtonState::Pressed) => {
+                if self.front_controller.handle_key_command(key, window) {
+                    return Ok(());
                 }
-                _ => {
-                    // self.front_controller.handle_key_command(key, window);
+                match key {
+                    Key::Escape => {
+                        if self.front_controller.close_requested() {
+                            window.close();
+                        }
+                    }
+                    Key::Z if is_command_down(window) => {
+                        if is_shift_down(window) {
+                            self.front_controller.redo();
+                        } else {
+                            self.front_controller.undo();
+                        }
+                    }
+                    _ => {}
                 }
-            },
+            }
             Event::Typed(c) => {
-                // self.front_controller.handle_key_press(*c, window);
+                // Typed characters include those of shortcuts, which are handled as key commands
+                if !is_command_down(window) {
+                    self.front_controller.handle_key_press(*c, window);
+                }
             }
             _ => {}
         };
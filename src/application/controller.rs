@@ -2,6 +2,7 @@ use super::*;
 
 use quicksilver::{
     geom::Vector,
+    input::Key,
     lifecycle::Window
 };
 
@@ -77,6 +78,36 @@ pub trait Controller {
     /// This is generally a passthru method to the Tweek gui controls
     fn handle_mouse_scroll(&mut self, _pt: &Vector, _state: &mut AppState) {}
 
+    /// A typed character, which goes to the control with keyboard focus. Eg: a TextField
+    fn handle_key_press(&mut self, _c: char, _window: &mut Window) {}
+
+    /// A pressed key, for editing keys such as Backspace and for shortcuts. Returns true if it was handled,
+    /// so that the AppDelegate does not handle it as well.
+    fn handle_key_command(&mut self, _key: &Key, _window: &mut Window) -> bool { false }
+
+    /// A controller to present modally over this one. The parent NavController takes it after
+    /// each update and presents it with present_controller.
+    fn next_modal(&mut self) -> Option<Box<dyn Controller>> { None }
+
+    /// For a modal controller, true when it is done and the NavController should remove it
+    fn is_dismissed(&self) -> bool { false }
+
+    /// Called when the user asks to quit, eg: with Escape. Returns true if the app can close now. Otherwise
+    /// the controller shows what it needs to, such as a modal to save changes, and closes the window itself.
+    fn close_requested(&mut self) -> bool { true }
+
+    /// Called every AUTOSAVE_INTERVAL_FRAMES from the AppDelegate update loop, to journal unsaved work
+    fn autosave(&mut self) {}
+
+    /// Called once at launch, to offer recovery of work journaled by a session that did not quit normally
+    fn check_recovery(&mut self) {}
+
+    /// Revert the last edit. Called for Ctrl+Z, or Cmd+Z on macOS.
+    fn undo(&mut self) {}
+
+    /// Apply the last undone edit again. Called for Shift+Ctrl+Z, or Shift+Cmd+Z on macOS.
+    fn redo(&mut self) {}
+
 
 }
 
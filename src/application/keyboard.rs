@@ -0,0 +1,20 @@
+/// Helpers for keyboard shortcuts. Shortcuts use Cmd on macOS and Ctrl elsewhere. Eg: Cmd+Z / Ctrl+Z
+///
+///
+use quicksilver::{input::Key, lifecycle::Window};
+
+/// True if Ctrl is held, or Cmd on macOS
+pub fn is_command_down(window: &Window) -> bool {
+    let keyboard = window.keyboard();
+    if cfg!(target_os = "macos") {
+        keyboard[Key::LWin].is_down() || keyboard[Key::RWin].is_down()
+    } else {
+        keyboard[Key::LControl].is_down() || keyboard[Key::RControl].is_down()
+    }
+}
+
+/// True if either Shift key is held
+pub fn is_shift_down(window: &Window) -> bool {
+    let keyboard = window.keyboard();
+    keyboard[Key::LShift].is_down() || keyboard[Key::RShift].is_down()
+}
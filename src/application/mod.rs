@@ -1,6 +1,7 @@
 pub use self::app::*;
 pub use self::app_delegate::*;
 pub use self::controller::*;
+pub use self::keyboard::*;
 // pub use self::nav_bar::*;
 pub use self::nav_controller::*;
 // pub use self::theme::*;
@@ -8,6 +9,7 @@ pub use self::nav_controller::*;
 mod app;
 mod app_delegate;
 mod controller;
+mod keyboard;
 // mod nav_bar;
 mod nav_controller;
 // mod theme;
@@ -6,6 +6,7 @@ use std::rc::Rc;
 use quicksilver::{
     geom::{Rectangle, Vector},
     // graphics::Color,
+    input::Key,
     lifecycle::Window
 };
 
@@ -192,16 +193,37 @@ impl Controller for NavController {
     fn update(&mut self, window: &mut Window, state: &mut AppState) {
         // Check only here that front_idx is not out of bounds.
         assert!(self.front_idx < self.controllers.len());
+        // The front controller keeps updating under a modal, so it can process what the modal queued
         let controller = &mut self.controllers[self.front_idx];
         controller.update(window, state);
+        let next_modal = controller.next_modal();
+
+        if let Some(modal) = &mut self.modal_controller {
+            modal.update(window, state);
+            if modal.is_dismissed() {
+                self.modal_controller = None;
+                self.transition = TransitionState::Completed;
+            }
+        }
+        if let Some(modal) = next_modal {
+            self.present_controller(modal, ModalDisplayStyle::None);
+        }
     }
 
     fn render(&mut self, theme: &mut Theme, window: &mut Window) {
         let controller = &mut self.controllers[self.front_idx];
         controller.render(theme, window);
+        if let Some(modal) = &mut self.modal_controller {
+            modal.render(theme, window);
+        }
     }
 
+    // While a modal controller is presented, it receives all mouse events
+
     fn handle_mouse_at(&mut self, pt: &Vector, window: &mut Window) -> bool {
+        if let Some(modal) = &mut self.modal_controller {
+            return modal.handle_mouse_at(pt, window);
+        }
         let controller = &mut self.controllers[self.front_idx];
         controller.handle_mouse_at(pt, window);
 
@@ -209,22 +231,84 @@ impl Controller for NavController {
     }
 
     fn handle_mouse_down(&mut self, pt: &Vector, state: &mut AppState) -> bool {
+        if let Some(modal) = &mut self.modal_controller {
+            return modal.handle_mouse_down(pt, state);
+        }
         let controller = &mut self.controllers[self.front_idx];
         controller.handle_mouse_down(pt, state);
         false
     }
 
     fn handle_mouse_up(&mut self, pt: &Vector, state: &mut AppState) -> bool {
+        if let Some(modal) = &mut self.modal_controller {
+            return modal.handle_mouse_up(pt, state);
+        }
         let controller = &mut self.controllers[self.front_idx];
         controller.handle_mouse_up(pt, state);
         false
     }
 
     fn handle_mouse_scroll(&mut self, pt: &Vector, state: &mut AppState) {
+        if let Some(modal) = &mut self.modal_controller {
+            return modal.handle_mouse_scroll(pt, state);
+        }
         let controller = &mut self.controllers[self.front_idx];
         controller.handle_mouse_scroll(pt, state);
     }
 
+    // Like mouse events, keys go only to the modal controller while one is presented
+
+    fn handle_key_press(&mut self, c: char, window: &mut Window) {
+        if let Some(modal) = &mut self.modal_controller {
+            return modal.handle_key_press(c, window);
+        }
+        let controller = &mut self.controllers[self.front_idx];
+        controller.handle_key_press(c, window);
+    }
+
+    fn handle_key_command(&mut self, key: &Key, window: &mut Window) -> bool {
+        if let Some(modal) = &mut self.modal_controller {
+            return modal.handle_key_command(key, window);
+        }
+        let controller = &mut self.controllers[self.front_idx];
+        controller.handle_key_command(key, window)
+    }
+
+    /// The front controller decides whether the app can close. While a modal controller is presented,
+    /// the request is ignored, so that the modal is answered first.
+    fn close_requested(&mut self) -> bool {
+        if self.modal_controller.is_some() {
+            return false;
+        }
+        self.controllers[self.front_idx].close_requested()
+    }
+
+    fn autosave(&mut self) {
+        for controller in self.controllers.iter_mut() {
+            controller.autosave();
+        }
+    }
+
+    fn check_recovery(&mut self) {
+        if let Some(controller) = self.controllers.get_mut(self.front_idx) {
+            controller.check_recovery();
+        }
+    }
+
+    // Undo and redo go to the front controller, and are ignored while a modal controller is presented
+
+    fn undo(&mut self) {
+        if self.modal_controller.is_none() {
+            self.controllers[self.front_idx].undo();
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.modal_controller.is_none() {
+            self.controllers[self.front_idx].redo();
+        }
+    }
+
 }
 
 #[derive(Debug, Clone, Copy)]
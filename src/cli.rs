@@ -0,0 +1,277 @@
+/// Headless commands for the app binary. These run without opening a window so they can be used
+/// from scripts and CI. Eg:
+///
+/// cargo run --bin app -- validate themes/my-theme.json
+///
+use crate::theme::*;
+
+use std::fs;
+
+use serde_json::json;
+
+const USAGE: &str = "Usage:
+  app                      Open the theme editor
+  app validate <file>...   Validate theme files and print the problems as JSON
+  app export-css <file> [--scss] [--prefix <prefix>] [--case kebab|snake|camel] [--out <file>]
+                           Export a theme as CSS custom properties, or SCSS variables with --scss
+  app contrast <file>... [--level aa|aaa]
+                           Print WCAG contrast reports as JSON. Fails if a color pair is below the level
+  app diff <before> <after>
+                           Print the field changes between two themes as JSON. Fails if they differ
+  app merge <base> <ours> <theirs> [--out <file>]
+                           Three-way merge of theme files. Prints conflicts as JSON and fails if there are any";
+
+/// Run the command given in args, which excludes the program name. Returns the process exit code,
+/// or None if no command was given and the editor should be launched.
+pub fn run_command(args: &[String]) -> Option<i32> {
+    let command = args.first()?;
+    let code = match command.as_str() {
+        "validate" => validate(&args[1..]),
+        "export-css" => export_css_command(&args[1..]),
+        "contrast" => contrast_command(&args[1..]),
+        "diff" => diff_command(&args[1..]),
+        "merge" => merge_command(&args[1..]),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            0
+        }
+        _ => usage_error(&format!("Unknown command {:?}", command)),
+    };
+    Some(code)
+}
+
+/// Print a JSON array with one entry per file. Exit code is 1 if any file has problems, or 2 if any
+/// file could not be read. A file that cannot be read has an "error" instead of "errors".
+fn validate(files: &[String]) -> i32 {
+    if files.is_empty() {
+        return usage_error("Missing theme file");
+    }
+    let mut code = 0;
+    let mut results: Vec<serde_json::Value> = Vec::new();
+    for file in files {
+        match validate_theme_file(file) {
+            Ok(errors) => {
+                if !errors.is_empty() {
+                    code = code.max(1);
+                }
+                results.push(json!({ "file": file, "errors": errors }));
+            }
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                code = 2;
+                results.push(json!({ "file": file, "error": err.to_string() }));
+            }
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&results).unwrap_or_default());
+    code
+}
+
+/// Export a theme file as CSS or SCSS. Writes to stdout unless --out is given.
+fn export_css_command(args: &[String]) -> i32 {
+    let mut file: Option<&String> = None;
+    let mut out: Option<&String> = None;
+    let mut scss = false;
+    let mut options = CssOptions::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--scss" => scss = true,
+            "--prefix" => match iter.next() {
+                Some(prefix) => options = options.with_prefix(prefix),
+                None => return usage_error("--prefix needs a value"),
+            },
+            "--case" => match iter.next().and_then(|name| NameCase::from_name(name)) {
+                Some(case) => options = options.with_case(case),
+                None => return usage_error("--case must be kebab, snake or camel"),
+            },
+            "--out" => match iter.next() {
+                Some(path) => out = Some(path),
+                None => return usage_error("--out needs a file"),
+            },
+            _ if file.is_none() => file = Some(arg),
+            _ => return usage_error(&format!("Unexpected argument {:?}", arg)),
+        }
+    }
+    let file = match file {
+        Some(file) => file,
+        None => return usage_error("Missing theme file"),
+    };
+    let document = match load_theme(file) {
+        Ok(document) => document,
+        Err(err) => {
+            eprintln!("{}: {}", file, err);
+            return 1;
+        }
+    };
+    let text = if scss { export_scss(&document, &options) } else { export_css(&document, &options) };
+    match out {
+        Some(path) => {
+            if let Err(err) = fs::write(path, text) {
+                eprintln!("{}: {}", path, err);
+                return 1;
+            }
+        }
+        None => print!("{}", text),
+    }
+    0
+}
+
+/// Print a JSON array with the contrast report of each file. Themes may extend each other or the
+/// built-in themes. Exit code is 1 if any color pair is below the level, which defaults to AA.
+fn contrast_command(args: &[String]) -> i32 {
+    let mut files: Vec<&String> = Vec::new();
+    let mut level = ContrastGrade::Aa;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--level" => match iter.next().and_then(|name| ContrastGrade::from_name(name)) {
+                Some(grade) => level = grade,
+                None => return usage_error("--level must be aa or aaa"),
+            },
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        return usage_error("Missing theme file");
+    }
+    let mut sources: Vec<ThemeSource> = Vec::new();
+    for file in &files {
+        match ThemeSource::load(file) {
+            Ok(source) => sources.push(source),
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                return 2;
+            }
+        }
+    }
+    let mut code = 0;
+    let mut results: Vec<serde_json::Value> = Vec::new();
+    for (file, result) in files.iter().zip(resolve_themes(&sources, &builtin_themes())) {
+        let document = match result {
+            Ok(document) => document,
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                return 2;
+            }
+        };
+        let report = contrast_report(&document.to_theme());
+        if !report.passes(level) {
+            code = 1;
+        }
+        results.push(json!({ "file": file, "passes": report.passes(level), "report": report }));
+    }
+    println!("{}", serde_json::to_string_pretty(&results).unwrap_or_default());
+    code
+}
+
+/// Print the changes between two theme files as JSON. Themes may extend each other or the built-in
+/// themes, and are compared by their resolved values. Exit code is 1 if they differ, like diff.
+fn diff_command(args: &[String]) -> i32 {
+    if args.len() != 2 {
+        return usage_error("diff needs two theme files");
+    }
+    let mut sources: Vec<ThemeSource> = Vec::new();
+    for file in args {
+        match ThemeSource::load(file) {
+            Ok(source) => sources.push(source),
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                return 2;
+            }
+        }
+    }
+    let mut documents: Vec<ThemeDocument> = Vec::new();
+    for (file, result) in args.iter().zip(resolve_themes(&sources, &builtin_themes())) {
+        match result {
+            Ok(document) => documents.push(document),
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                return 2;
+            }
+        }
+    }
+    let diff = match diff_themes(&documents[0], &documents[1]) {
+        Ok(diff) => diff,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 2;
+        }
+    };
+    let result = json!({ "before": args[0], "after": args[1], "changes": diff.changes });
+    println!("{}", serde_json::to_string_pretty(&result).unwrap_or_default());
+    if diff.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Merge the changes to two copies of a theme file. The merged file is written to --out, or printed
+/// with the conflicts if there is no --out. Conflicting fields keep our value. Exit code is 1 if there
+/// are conflicts. This works as a git merge driver:
+///
+/// git config merge.theme.driver "app merge %O %A %B --out %A"
+fn merge_command(args: &[String]) -> i32 {
+    let mut files: Vec<&String> = Vec::new();
+    let mut out: Option<&String> = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--out" => match iter.next() {
+                Some(file) => out = Some(file),
+                None => return usage_error("--out needs a file"),
+            },
+            _ => files.push(arg),
+        }
+    }
+    if files.len() != 3 {
+        return usage_error("merge needs base, ours and theirs theme files");
+    }
+    let mut values: Vec<serde_json::Value> = Vec::new();
+    for file in &files {
+        let value = fs::read_to_string(file)
+            .map_err(ThemeError::from)
+            .and_then(|json| serde_json::from_str(&json).map_err(ThemeError::from));
+        match value {
+            Ok(value) => values.push(value),
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                return 2;
+            }
+        }
+    }
+    let merge = merge_theme_values(&values[0], &values[1], &values[2]);
+    if let Err(err) = merge.to_source() {
+        eprintln!("The merged theme is not valid: {}", err);
+        return 2;
+    }
+    let result = match out {
+        Some(out) => {
+            let json = serde_json::to_string_pretty(&merge.merged).unwrap_or_default();
+            if let Err(err) = fs::write(out, json) {
+                eprintln!("Could not write {}: {}", out, err);
+                return 2;
+            }
+            json!({ "conflicts": merge.conflicts })
+        }
+        None => json!({ "conflicts": merge.conflicts, "merged": merge.merged }),
+    };
+    println!("{}", serde_json::to_string_pretty(&result).unwrap_or_default());
+    if merge.conflicts.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Load a theme file. A theme that extends another theme is resolved against the built-in themes.
+fn load_theme(file: &str) -> Result<ThemeDocument, ThemeError> {
+    let source = ThemeSource::load(file)?;
+    resolve_themes(&[source], &builtin_themes()).remove(0)
+}
+
+fn usage_error(message: &str) -> i32 {
+    eprintln!("{}\n{}", message, USAGE);
+    2
+}
@@ -0,0 +1,344 @@
+/// The color picker in the Properties scene. The picker keeps its color in HSV so that the hue is
+/// not lost while the saturation or value is zero. Its controls are plain buttons which queue
+/// PickerActions, so the picker is rebuilt with the rest of the stage after each change.
+///
+/// V:|-[field swatches]-|-[hue/saturation area]-|-[value strip]-|-[alpha strip]-|-[hex]-|-[rgb]-|-[hsl]-|-[recent]-|
+///
+use super::*;
+use crate::theme::*;
+
+use quicksilver::{geom::Rectangle, graphics::Color};
+
+use tweek::gui::*;
+
+/// The most recent colors kept by the picker
+pub const RECENT_COLORS_MAX: usize = 12;
+const HUE_STEPS: usize = 12;
+const SATURATION_STEPS: usize = 6;
+const STRIP_STEPS: usize = 10;
+const CELL_HEIGHT: f32 = 14.0;
+const ROW_HEIGHT: f32 = 20.0;
+const STEP_BUTTON_WIDTH: f32 = 24.0;
+
+/// A text format for entering colors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorFormat {
+    /// #RRGGBB or #RRGGBBAA
+    Hex,
+    /// Red, green and blue from 0 to 255. Eg: 42, 162, 152
+    Rgb,
+    /// Hue in degrees and saturation and lightness in percent. Eg: 174, 59%, 40%
+    Hsl,
+}
+
+impl ColorFormat {
+    pub const ALL: [ColorFormat; 3] = [ColorFormat::Hex, ColorFormat::Rgb, ColorFormat::Hsl];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorFormat::Hex => "Hex",
+            ColorFormat::Rgb => "RGB",
+            ColorFormat::Hsl => "HSL",
+        }
+    }
+
+    /// The channel names and step sizes of the stepper buttons
+    fn steps(&self) -> &'static [(&'static str, f32)] {
+        match self {
+            ColorFormat::Hex => &[],
+            ColorFormat::Rgb => &[("R", 5.0), ("G", 5.0), ("B", 5.0)],
+            ColorFormat::Hsl => &[("H", 10.0), ("S", 5.0), ("L", 5.0)],
+        }
+    }
+}
+
+/// Changes made with the picker controls
+#[derive(Debug, Clone, PartialEq)]
+pub enum PickerAction {
+    /// Edit another color field of the theme. Eg: bg_color
+    SelectField(&'static str),
+    /// Set the hue in degrees and the saturation from 0 to 1
+    HueSaturation(f32, f32),
+    /// Set the HSV value from 0 to 1
+    Value(f32),
+    Alpha(f32),
+    /// Add to a channel of a format, by index. Eg: (Rgb, 0, 5.0) adds 5 to red
+    Step(ColorFormat, usize, f32),
+    /// Use a color from the recent colors strip
+    Recent(Color),
+    /// Start typing a color in a format
+    BeginEntry(ColorFormat),
+    /// Parse the typed text and use the color
+    CommitEntry,
+    CancelEntry,
+}
+
+/// The state of the color picker
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorPicker {
+    /// The color field being edited. Eg: bg_color
+    pub field: &'static str,
+    hsv: Hsv,
+    recent: Vec<Color>,
+    /// The format and text being typed, if any
+    entry: Option<(ColorFormat, String)>,
+}
+
+impl ColorPicker {
+    pub fn new(field: &'static str, color: Color) -> Self {
+        ColorPicker { field, hsv: Hsv::from_color(&color), recent: Vec::new(), entry: None }
+    }
+
+    pub fn color(&self) -> Color {
+        self.hsv.to_color()
+    }
+
+    /// Change the color, keeping the current hue if the new color is gray
+    pub fn set_color(&mut self, color: Color) {
+        if color == self.color() {
+            return;
+        }
+        let hsv = Hsv::from_color(&color);
+        self.hsv = if hsv.s == 0.0 || hsv.v == 0.0 { Hsv { h: self.hsv.h, ..hsv } } else { hsv };
+    }
+
+    pub fn recent_colors(&self) -> &[Color] {
+        &self.recent
+    }
+
+    /// Add a color to the front of the recent colors
+    pub fn remember(&mut self, color: Color) {
+        self.recent.retain(|recent| *recent != color);
+        self.recent.insert(0, color);
+        self.recent.truncate(RECENT_COLORS_MAX);
+    }
+
+    /// The text being typed, if any
+    pub fn entry(&self) -> Option<&(ColorFormat, String)> {
+        self.entry.as_ref()
+    }
+
+    /// Apply an action from the picker controls. Returns true if the color changed.
+    pub fn apply(&mut self, action: PickerAction) -> bool {
+        let before = self.color();
+        match action {
+            PickerAction::SelectField(field) => self.field = field,
+            PickerAction::HueSaturation(h, s) => self.hsv = Hsv { h, s, ..self.hsv },
+            PickerAction::Value(v) => self.hsv.v = v,
+            PickerAction::Alpha(a) => self.hsv.a = a,
+            PickerAction::Step(format, channel, amount) => {
+                let color = step_channel(&self.color(), format, channel, amount);
+                self.set_color(color);
+            }
+            PickerAction::Recent(color) => self.set_color(color),
+            PickerAction::BeginEntry(format) => self.entry = Some((format, self.format_text(format))),
+            PickerAction::CommitEntry => {
+                let color = self.entry.take().and_then(|(format, text)| parse_color_text(format, &text));
+                if let Some(color) = color {
+                    self.set_color(color);
+                }
+            }
+            PickerAction::CancelEntry => self.entry = None,
+        }
+        let changed = self.color() != before;
+        if changed {
+            self.remember(before);
+        }
+        changed
+    }
+
+    /// Add typed characters to the entry being edited
+    pub fn type_text(&mut self, text: &str) {
+        if let Some((_, entry)) = &mut self.entry {
+            entry.push_str(text);
+        }
+    }
+
+    /// Remove the last typed character
+    pub fn backspace(&mut self) {
+        if let Some((_, entry)) = &mut self.entry {
+            entry.pop();
+        }
+    }
+
+    /// The color as text in a format
+    pub fn format_text(&self, format: ColorFormat) -> String {
+        format_color_text(&self.color(), format)
+    }
+
+    /// Add the picker controls to a scene, inside frame, which is in scene coordinates. The button
+    /// function makes a button that queues an action. Returns the height used.
+    pub fn add_controls<F>(&self, scene: &mut Scene, frame: &Rectangle, button: F) -> f32
+    where
+        F: Fn(Rectangle, &str, EditorAction) -> Button,
+    {
+        let (x, width) = (frame.x(), frame.width());
+        let mut ypos = frame.y();
+        type Point = (f32, f32);
+        let picker_button = |scene: &mut Scene, origin: Point, size: Point, title: &str, action: PickerAction| {
+            let frame = scene.sub_frame(origin, size);
+            let mut control = button(frame, title, EditorAction::Picker(action));
+            control.layer.font_style = FontStyle::new(12.0, Color::BLACK);
+            control
+        };
+        let swatch =
+            |scene: &mut Scene, origin: Point, size: Point, color: Color, selected: bool, action: PickerAction| {
+                let mut control = picker_button(scene, origin, size, "", action);
+                control.layer.bg_style = BackgroundStyle::Solid(color);
+                control.layer.border_style = if selected {
+                    BorderStyle::SolidLine(Color::BLACK, 2.0)
+                } else {
+                    BorderStyle::SolidLine(Color::from_hex("#CCCCCC"), 1.0)
+                };
+                control.layer.lock_style = true;
+                scene.add_control(Box::new(control));
+            };
+        let label = |scene: &mut Scene, origin: Point, size: Point, value: &str| {
+            let mut text = Text::new(scene.sub_frame(origin, size), value);
+            text.layer.font_style = FontStyle::new(12.0, Color::BLACK);
+            text.text_align(TextAlign::Left);
+            scene.add_control(Box::new(text));
+        };
+
+        // The theme colors, to choose the field being edited
+        label(scene, (x, ypos), (width, ROW_HEIGHT), self.field);
+        ypos += ROW_HEIGHT;
+        let cell_width = width / ThemeColors::FIELDS.len() as f32;
+        for (i, field) in ThemeColors::FIELDS.iter().enumerate() {
+            let color = if *field == self.field { self.color() } else { Color::from_hex("#EEEEEE") };
+            let origin = (x + cell_width * i as f32, ypos);
+            let selected = *field == self.field;
+            swatch(scene, origin, (cell_width, ROW_HEIGHT), color, selected, PickerAction::SelectField(*field));
+        }
+        ypos += ROW_HEIGHT + 5.0;
+
+        // Hue across, saturation down, at the current value
+        let cell_width = width / HUE_STEPS as f32;
+        for row in 0..SATURATION_STEPS {
+            let s = 1.0 - row as f32 / (SATURATION_STEPS - 1) as f32;
+            for column in 0..HUE_STEPS {
+                let h = 360.0 * column as f32 / HUE_STEPS as f32;
+                let color = Hsv { h, s, v: self.hsv.v, a: 1.0 }.to_color();
+                let selected = (self.hsv.h - h).abs() < 15.0 && (self.hsv.s - s).abs() < 0.1;
+                let origin = (x + cell_width * column as f32, ypos + CELL_HEIGHT * row as f32);
+                swatch(scene, origin, (cell_width, CELL_HEIGHT), color, selected, PickerAction::HueSaturation(h, s));
+            }
+        }
+        ypos += CELL_HEIGHT * SATURATION_STEPS as f32 + 5.0;
+
+        // Value and alpha strips, from low to high
+        let cell_width = width / STRIP_STEPS as f32;
+        for i in 0..STRIP_STEPS {
+            let amount = (i + 1) as f32 / STRIP_STEPS as f32;
+            let origin = (x + cell_width * i as f32, ypos);
+            let color = Hsv { v: amount, a: 1.0, ..self.hsv }.to_color();
+            let selected = (self.hsv.v - amount).abs() < 0.05;
+            swatch(scene, origin, (cell_width, CELL_HEIGHT), color, selected, PickerAction::Value(amount));
+            let origin = (x + cell_width * i as f32, ypos + CELL_HEIGHT + 2.0);
+            let color = Hsv { a: amount, ..self.hsv }.to_color();
+            let selected = (self.hsv.a - amount).abs() < 0.05;
+            swatch(scene, origin, (cell_width, CELL_HEIGHT), color, selected, PickerAction::Alpha(amount));
+        }
+        ypos += CELL_HEIGHT * 2.0 + 7.0;
+
+        // One row per text format, with a button to type the color and steppers per channel
+        for format in ColorFormat::ALL.iter() {
+            let value = match &self.entry {
+                Some((entry_format, text)) if entry_format == format => format!("{}|", text),
+                _ => self.format_text(*format),
+            };
+            let title = format!("{} {}", format.name(), value);
+            let action = PickerAction::BeginEntry(*format);
+            let control = picker_button(scene, (x, ypos), (width, ROW_HEIGHT), &title, action);
+            scene.add_control(Box::new(control));
+            ypos += ROW_HEIGHT;
+            for (channel, (name, step)) in format.steps().iter().enumerate() {
+                for (j, (sign, amount)) in [("-", -step), ("+", *step)].iter().enumerate() {
+                    let origin = (x + STEP_BUTTON_WIDTH * (channel * 2 + j) as f32, ypos);
+                    let title = format!("{}{}", name, sign);
+                    let action = PickerAction::Step(*format, channel, *amount);
+                    let mut control = picker_button(scene, origin, (STEP_BUTTON_WIDTH, ROW_HEIGHT), &title, action);
+                    control.layer.font_style = FontStyle::new(10.0, Color::BLACK);
+                    scene.add_control(Box::new(control));
+                }
+            }
+            if !format.steps().is_empty() {
+                ypos += ROW_HEIGHT;
+            }
+        }
+        if self.entry.is_some() {
+            let size = (width / 2.0, ROW_HEIGHT);
+            let control = picker_button(scene, (x, ypos), size, "Apply", PickerAction::CommitEntry);
+            scene.add_control(Box::new(control));
+            let control = picker_button(scene, (x + width / 2.0, ypos), size, "Cancel", PickerAction::CancelEntry);
+            scene.add_control(Box::new(control));
+            ypos += ROW_HEIGHT;
+        }
+        ypos += 5.0;
+
+        // Recent colors, most recent first
+        if !self.recent.is_empty() {
+            let cell_width = width / RECENT_COLORS_MAX as f32;
+            for (i, color) in self.recent.iter().enumerate() {
+                let origin = (x + cell_width * i as f32, ypos);
+                swatch(scene, origin, (cell_width, ROW_HEIGHT), *color, false, PickerAction::Recent(*color));
+            }
+            ypos += ROW_HEIGHT + 5.0;
+        }
+        ypos - frame.y()
+    }
+}
+
+/// A color as text in a format. Eg: "#2AA298", "42, 162, 152" or "174, 59%, 40%"
+pub fn format_color_text(color: &Color, format: ColorFormat) -> String {
+    match format {
+        ColorFormat::Hex => hex_string(color),
+        ColorFormat::Rgb => {
+            let channel = |c: f32| (c * 255.0).round() as u8;
+            format!("{}, {}, {}", channel(color.r), channel(color.g), channel(color.b))
+        }
+        ColorFormat::Hsl => {
+            let hsl = Hsl::from_color(color);
+            format!("{}, {}%, {}%", hsl.h.round(), (hsl.s * 100.0).round(), (hsl.l * 100.0).round())
+        }
+    }
+}
+
+/// Parse a color typed in a format. The alpha is 1 except for hex colors with an alpha.
+pub fn parse_color_text(format: ColorFormat, text: &str) -> Option<Color> {
+    if format == ColorFormat::Hex {
+        let text = text.trim();
+        return if text.starts_with('#') { parse_hex_color(text) } else { parse_hex_color(&format!("#{}", text)) };
+    }
+    let parse = |part: &str| part.trim().trim_end_matches('%').trim().parse::<f32>();
+    let values: Vec<f32> = text.split(',').map(parse).collect::<Result<_, _>>().ok()?;
+    if values.len() != 3 {
+        return None;
+    }
+    let color = match format {
+        ColorFormat::Rgb => Color { r: values[0] / 255.0, g: values[1] / 255.0, b: values[2] / 255.0, a: 1.0 },
+        _ => Hsl { h: values[0], s: values[1] / 100.0, l: values[2] / 100.0, a: 1.0 }.to_color(),
+    };
+    Some(clamp_to_gamut(&color))
+}
+
+/// Add to one channel of a color in a format, keeping the alpha
+fn step_channel(color: &Color, format: ColorFormat, channel: usize, amount: f32) -> Color {
+    match format {
+        ColorFormat::Hex => *color,
+        ColorFormat::Rgb => {
+            let mut channels = [color.r, color.g, color.b];
+            channels[channel] = (channels[channel] + amount / 255.0).max(0.0).min(1.0);
+            Color { r: channels[0], g: channels[1], b: channels[2], a: color.a }
+        }
+        ColorFormat::Hsl => {
+            let mut hsl = Hsl::from_color(color);
+            match channel {
+                0 => hsl.h = (hsl.h + amount + 360.0) % 360.0,
+                1 => hsl.s = (hsl.s + amount / 100.0).max(0.0).min(1.0),
+                _ => hsl.l = (hsl.l + amount / 100.0).max(0.0).min(1.0),
+            }
+            hsl.to_color()
+        }
+    }
+}
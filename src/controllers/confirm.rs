@@ -0,0 +1,169 @@
+/// A modal dialog that asks the user to confirm an editor action. It is presented over the editor
+/// with NavController::present_controller and queues the chosen action in the editor's action queue:
+///
+/// let dialog = ConfirmController::new(frame, "Delete theme?", "This cannot be undone.", actions)
+///     .with_choice("Delete", Some(EditorAction::ConfirmDelete(id)))
+///     .with_choice("Cancel", None);
+///
+/// Escape chooses the choice without an action, if there is one.
+///
+use crate::application::*;
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use quicksilver::{
+    geom::{Rectangle, Vector},
+    graphics::Color,
+    input::Key,
+    lifecycle::Window,
+};
+
+use tweek::{
+    core::AppState,
+    events::*,
+    gui::*,
+};
+
+use super::EditorAction;
+
+const DIALOG_WIDTH: f32 = 360.0;
+const DIALOG_HEIGHT: f32 = 160.0;
+const DIALOG_BUTTON_WIDTH: f32 = 90.0;
+const DIALOG_BUTTON_HEIGHT: f32 = 32.0;
+
+pub struct ConfirmController {
+    frame: Rectangle,
+    stage: Stage,
+    title: String,
+    message: String,
+    /// The button titles from left to right, with the action each one queues. None only dismisses.
+    choices: Vec<(String, Option<EditorAction>)>,
+    actions: Rc<RefCell<Vec<EditorAction>>>,
+    dismissed: Rc<Cell<bool>>,
+}
+
+impl ConfirmController {
+    /// Create a dialog which queues the chosen action in the given action queue
+    pub fn new(frame: Rectangle, title: &str, message: &str, actions: Rc<RefCell<Vec<EditorAction>>>) -> Self {
+        ConfirmController {
+            stage: Stage::new(frame.clone()),
+            frame,
+            title: title.to_string(),
+            message: message.to_string(),
+            choices: Vec::new(),
+            actions,
+            dismissed: Rc::new(Cell::new(false)),
+        }
+    }
+
+    /// Builder method to add a button to the dialog
+    pub fn with_choice(mut self, title: &str, action: Option<EditorAction>) -> Self {
+        self.choices.push((title.to_string(), action));
+        self
+    }
+
+    /// Define the dialog layout, centered over a dimmed copy of the frame
+    /// V:|-[title]-|-[message]-|-
+    ///     H:|-[choice]-|-[choice]-|-...
+    fn build_stage(&self) -> Stage {
+        let mut stage = Stage::new(self.frame.clone());
+
+        let mut overlay = Scene::new(self.frame.clone()).with_id(1, "Overlay");
+        overlay.layer.bg_style = BackgroundStyle::Solid(Color { r: 0.0, g: 0.0, b: 0.0, a: 0.4 });
+        overlay.layer.lock_style = true;
+        stage.add_scene(overlay);
+
+        let origin = (
+            self.frame.x() + (self.frame.width() - DIALOG_WIDTH) / 2.0,
+            self.frame.y() + (self.frame.height() - DIALOG_HEIGHT) / 2.0,
+        );
+        let mut scene = Scene::new(Rectangle::new(origin, (DIALOG_WIDTH, DIALOG_HEIGHT))).with_id(2, "Dialog");
+        scene.layer.bg_style = BackgroundStyle::Solid(Color::WHITE);
+        scene.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 1.0);
+
+        let subframe = scene.sub_frame((10.0, 10.0), (DIALOG_WIDTH - 20.0, 30.0));
+        let mut text = Text::new(subframe, &self.title);
+        text.layer.font_style = FontStyle::new(16.0, Color::BLACK);
+        text.text_align(TextAlign::Left);
+        scene.add_control(Box::new(text));
+
+        let subframe = scene.sub_frame((10.0, 50.0), (DIALOG_WIDTH - 20.0, 50.0));
+        let mut text = Text::new(subframe, &self.message);
+        text.layer.font_style = FontStyle::new(12.0, Color::from_hex("#333333"));
+        text.text_align(TextAlign::Left);
+        scene.add_control(Box::new(text));
+
+        // Buttons are right aligned, in the order they were added
+        let ypos = DIALOG_HEIGHT - DIALOG_BUTTON_HEIGHT - 10.0;
+        let mut xpos = DIALOG_WIDTH - 10.0 - (DIALOG_BUTTON_WIDTH + 10.0) * self.choices.len() as f32 + 10.0;
+        for (title, action) in &self.choices {
+            let subframe = scene.sub_frame((xpos, ypos), (DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT));
+            let mut button = Button::new(subframe).with_text(title);
+            button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 1.0);
+            let action = action.clone();
+            let actions = self.actions.clone();
+            let dismissed = self.dismissed.clone();
+            button.set_onclick(move |_state: &mut AppState| {
+                if let Some(action) = &action {
+                    actions.borrow_mut().push(action.clone());
+                }
+                dismissed.set(true);
+            });
+            scene.add_control(Box::new(button));
+            xpos += DIALOG_BUTTON_WIDTH + 10.0;
+        }
+        stage.add_scene(scene);
+
+        stage
+    }
+}
+
+impl Controller for ConfirmController {
+    fn view_will_load(&mut self) {
+        self.stage = self.build_stage();
+        self.stage.notify(&DisplayEvent::Ready);
+    }
+
+    fn set_theme(&mut self, theme: &mut Theme) {
+        self.stage.set_theme(theme);
+    }
+
+    fn screen_title(&self) -> &str {
+        &self.title
+    }
+
+    fn is_dismissed(&self) -> bool {
+        self.dismissed.get()
+    }
+
+    fn update(&mut self, window: &mut Window, state: &mut AppState) {
+        let _ = self.stage.update(window, state);
+    }
+
+    fn render(&mut self, theme: &mut Theme, window: &mut Window) {
+        let _ = self.stage.render(theme, window);
+    }
+
+    fn handle_mouse_at(&mut self, pt: &Vector, window: &mut Window) -> bool {
+        self.stage.handle_mouse_at(pt, window)
+    }
+
+    fn handle_mouse_down(&mut self, pt: &Vector, state: &mut AppState) -> bool {
+        self.stage.handle_mouse_down(pt, state)
+    }
+
+    fn handle_mouse_up(&mut self, pt: &Vector, state: &mut AppState) -> bool {
+        self.stage.handle_mouse_up(pt, state)
+    }
+
+    fn handle_key_command(&mut self, key: &Key, _window: &mut Window) -> bool {
+        match key {
+            Key::Escape if self.choices.iter().any(|(_, action)| action.is_none()) => {
+                self.dismissed.set(true);
+                true
+            }
+            _ => false,
+        }
+    }
+}
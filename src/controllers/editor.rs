@@ -1,9 +1,17 @@
 use crate::application::*;
+use crate::theme::*;
 use crate::utils::*;
 
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
 use quicksilver::{
     geom::{Rectangle, Vector},
     graphics::{Color},
+    input::Key,
     lifecycle::{Window},
 };
 
@@ -25,12 +33,202 @@ const COL1_WIDTH: f32 = 200.0;
 const COL2_WIDTH: f32 = 600.0;
 const COL3_WIDTH: f32 = 200.0;
 const MINI_BUTTON_SIZE: f32 = 32.0;
+const FILE_BUTTON_WIDTH: f32 = 60.0;
+const PROPERTY_ROW_HEIGHT: f32 = 20.0;
+const SWATCH_SIZE: f32 = 30.0;
+const SWATCHES_PER_ROW: usize = 6;
+const INSPECTOR_LABEL_WIDTH: f32 = 100.0;
+const MIN_FONT_SIZE: f32 = 1.0;
+const GALLERY_SPACING: f32 = 5.0;
+/// The color of fields that differ from the compared theme
+const COMPARE_COLOR: Color = Color { r: 0.0, g: 0.4, b: 0.8, a: 1.0 };
+const GALLERY_ROWS: [&str; 5] = ["First row", "Second row", "Third row", "Fourth row", "Fifth row"];
+const GALLERY_OPTIONS: [&str; 3] = ["First option", "Second option", "Third option"];
+/// The directory where theme files are opened from and saved to
+pub const THEMES_DIR: &str = "themes";
+/// The subdirectory of THEMES_DIR where VS Code color themes are imported from
+pub const VSCODE_DIR: &str = "vscode";
+/// An optional VscodeMapping file in VSCODE_DIR which replaces the default mapping
+pub const VSCODE_MAPPING_FILE: &str = "mapping.json";
+/// The subdirectory of THEMES_DIR where CSS and SCSS exports are written
+pub const CSS_DIR: &str = "css";
+/// The subdirectory of THEMES_DIR where generated Rust modules are written
+pub const RUST_DIR: &str = "rust";
+/// The directory with the font files that can be chosen in the property inspector
+pub const FONTS_DIR: &str = "static";
+/// The first id given to themes created in the editor
+pub const FIRST_CUSTOM_THEME_ID: u32 = 100;
+
+/// Actions triggered by controls in the editor. These are queued by button callbacks and
+/// processed in the next update.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditorAction {
+    /// Load every theme file in the themes directory
+    OpenThemes,
+    /// Save the current theme to the themes directory
+    SaveTheme,
+    /// Import every VS Code color theme in the vscode subdirectory
+    ImportVscode,
+    /// Export the current theme as a VS Code color theme in the vscode subdirectory
+    ExportVscode,
+    /// Export the current theme as CSS and SCSS files in the css subdirectory
+    ExportCss,
+    /// Export the current theme as a Rust module in the rust subdirectory
+    ExportRust,
+    /// Show the Main scene as seen with a color vision deficiency, or normally if None
+    PreviewFilter(Option<CvdKind>),
+    /// Open or close the "New theme from color…" form in the Properties scene
+    NewTheme,
+    /// Set the seed color of the new theme
+    PaletteSeed(Color),
+    /// Set or clear the second seed color of the new theme
+    PaletteSecondSeed(Option<Color>),
+    PaletteHarmony(Harmony),
+    /// Make the new theme dark or light
+    PaletteDark(bool),
+    /// Add the generated theme and make it the current theme
+    CreateFromPalette,
+    /// A change made with the color picker in the Properties scene
+    Picker(PickerAction),
+    /// Add to a numeric field of the current theme. Eg: ("font_size", 1.0)
+    StepField(&'static str, f32),
+    /// Show or hide the options of a field in the property inspector
+    ToggleDropdown(&'static str),
+    /// Set a field of the current theme to an option chosen in the property inspector
+    SetField(&'static str, FieldValue),
+    /// Make a registered theme the current theme, by id
+    SelectTheme(u32),
+    /// Start typing a new name for the current theme
+    BeginRename,
+    /// Rename the current theme to the typed name
+    CommitRename,
+    CancelRename,
+    /// Show or hide the menu of ways to create a theme in the Explorer scene
+    ToggleCreateMenu,
+    /// Add a new theme and make it the current theme
+    CreateTheme(NewThemeKind),
+    /// Ask to delete the current theme
+    DeleteTheme,
+    /// Delete a theme, after it was confirmed
+    ConfirmDelete(u32),
+    /// Revert the last theme edit, addition or deletion
+    Undo,
+    /// Apply the last undone command again
+    Redo,
+    /// Quit the app, after saving every theme with unsaved changes if save is true
+    Quit { save: bool },
+    /// Restore the themes in the recovery journal from a session that did not shut down cleanly, or
+    /// discard them if false
+    Recover(bool),
+    /// Draw every control in the Main scene gallery in an interaction state
+    PreviewState(ControlState),
+    /// Set or clear a state color override of the current theme. Eg: (Hover, "bg_color", Some(color))
+    SetStateColor(ControlState, &'static str, Option<Color>),
+    /// Compare the current theme with the theme of the given id side by side, or end compare mode
+    Compare(Option<u32>),
+}
+
+/// The controls in the Main scene gallery
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GalleryItem {
+    Title,
+    Body,
+    NormalButton,
+    PrimaryButton,
+    EmptyField,
+    FilledField,
+    TextArea,
+    ListBox,
+    Unchecked,
+    Checked,
+    Options,
+}
+
+/// The ways to create a theme from the "+" menu in the Explorer scene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NewThemeKind {
+    /// A complete theme with the default Tweek theme values
+    Blank,
+    /// A copy of the current theme
+    Duplicate,
+    /// A theme that extends a built-in theme, by id, without overriding any fields
+    Template(u32),
+}
+
+/// The settings of the "New theme from color…" form. While the form is open, the Main scene
+/// previews the generated theme.
+#[derive(Debug, Clone, PartialEq)]
+struct PaletteForm {
+    seed: Color,
+    second: Option<Color>,
+    options: PaletteOptions,
+}
+
+impl PaletteForm {
+    fn palette(&self) -> Palette {
+        Palette::generate(self.seed, self.second, &self.options)
+    }
+}
 
 #[allow(dead_code)]
 pub struct ThemeEditor {
     frame: Rectangle,
     stage: Stage,
     theme_picker: ThemePicker,
+    /// The documents of the themes registered in the ThemePicker, in the order they were added
+    themes: Vec<ThemeDocument>,
+    /// The theme being edited
+    document: ThemeDocument,
+    themes_dir: PathBuf,
+    /// Problems from the last Open, listed in the Properties scene
+    load_errors: Vec<(PathBuf, ThemeError)>,
+    /// Problems from the last evaluation of the current theme's color expressions
+    expression_errors: Vec<ExprError>,
+    /// The contrast of the current theme, with warnings shown in the Properties scene
+    contrast: ContrastReport,
+    /// The color vision deficiency simulated in the preview, if any. The theme itself is unchanged.
+    preview_filter: Option<CvdKind>,
+    /// The interaction state every gallery control is drawn in
+    preview_state: ControlState,
+    /// In compare mode, the id of the theme shown next to the current theme
+    compare_id: Option<u32>,
+    /// The open "New theme from color…" form, if any
+    palette_form: Option<PaletteForm>,
+    /// The color picker in the Properties scene, which edits one color field of the current theme
+    color_picker: ColorPicker,
+    /// The field whose options are shown in the property inspector, if any
+    open_dropdown: Option<&'static str>,
+    /// The new name being typed for the current theme, if any
+    name_entry: Option<String>,
+    /// The font files in FONTS_DIR, offered as options for the font fields
+    font_files: Vec<String>,
+    /// The fonts read from FONTS_DIR, by the default and title file names. None if they could not be read.
+    font_cache: BTreeMap<(String, String), Option<FontData>>,
+    /// The ids of themes with changes that have not been saved
+    dirty: BTreeSet<u32>,
+    /// True while the menu of ways to create a theme is shown
+    create_menu: bool,
+    /// The commands for undo and redo of theme edits, additions and deletions
+    history: ThemeHistory,
+    /// The number of updates so far, used to coalesce rapid edits in the history
+    frames: u64,
+    /// The journal last written by autosave, to skip writing it again when nothing changed
+    journal: Option<RecoveryJournal>,
+    /// A journal found at launch, waiting for the user to restore or discard it
+    recovery: Option<RecoveryJournal>,
+    /// The buttons that can take keyboard focus, filled by action_button while the stage is built
+    focus: RefCell<FocusChain>,
+    /// A confirmation dialog waiting to be presented by the NavController
+    modal: Option<Box<dyn Controller>>,
+    /// The id for the next theme added to the ThemePicker
+    next_id: u32,
+    /// The ids of the themes imported from VS Code theme files, by file
+    imported: BTreeMap<PathBuf, u32>,
+    /// The files of themes that were renamed after they were saved, by id. The file is removed when the
+    /// theme is saved under its new name.
+    renamed_files: BTreeMap<u32, PathBuf>,
+    css_options: CssOptions,
+    actions: Rc<RefCell<Vec<EditorAction>>>,
 }
 
 impl ThemeEditor {
@@ -46,14 +244,801 @@ impl ThemeEditor {
             theme
         });
 
+        let themes = builtin_themes();
+        let document = themes[0].clone();
+        let contrast = contrast_report(&document.to_theme());
+        let color_picker = ColorPicker::new("bg_color", document.colors.bg_color);
+
         let controller = ThemeEditor {
             frame,
             stage,
             theme_picker,
+            themes,
+            document,
+            themes_dir: PathBuf::from(THEMES_DIR),
+            load_errors: Vec::new(),
+            expression_errors: Vec::new(),
+            contrast,
+            preview_filter: None,
+            preview_state: ControlState::Normal,
+            compare_id: None,
+            palette_form: None,
+            color_picker,
+            open_dropdown: None,
+            name_entry: None,
+            font_files: font_files(Path::new(FONTS_DIR)),
+            font_cache: BTreeMap::new(),
+            dirty: BTreeSet::new(),
+            create_menu: false,
+            history: ThemeHistory::new(),
+            frames: 0,
+            journal: None,
+            recovery: None,
+            focus: RefCell::new(FocusChain::new()),
+            modal: None,
+            next_id: FIRST_CUSTOM_THEME_ID,
+            imported: BTreeMap::new(),
+            renamed_files: BTreeMap::new(),
+            css_options: CssOptions::default(),
+            actions: Rc::new(RefCell::new(Vec::new())),
         };
         controller
     }
 
+    /// Builder method to open and save themes in a different directory
+    pub fn with_themes_dir(mut self, dir: PathBuf) -> Self {
+        self.themes_dir = dir;
+        self
+    }
+
+    /// Builder method to change the variable names used by Export CSS
+    pub fn with_css_options(mut self, options: CssOptions) -> Self {
+        self.css_options = options;
+        self
+    }
+
+    /// Load all theme files from the themes directory and register them in the ThemePicker.
+    /// The last theme loaded becomes the current theme. Files with validation problems are skipped
+    /// and their problems are shown in the Properties scene. Loaded themes replace the edited ones, so
+    /// the undo history is cleared.
+    fn open_themes(&mut self) {
+        self.load_errors.clear();
+        self.history.clear();
+        let files = match theme_files(&self.themes_dir) {
+            Ok(files) => files,
+            Err(err) => {
+                log::error!("Could not read {:?}: {}", self.themes_dir, err);
+                return;
+            }
+        };
+        let mut paths: Vec<PathBuf> = Vec::new();
+        let mut sources: Vec<ThemeSource> = Vec::new();
+        for path in files {
+            match ThemeSource::load(&path) {
+                Ok(source) => {
+                    paths.push(path);
+                    sources.push(source);
+                }
+                Err(err) => {
+                    log::error!("Could not load {:?}: {}", path, err);
+                    self.load_errors.push((path, err));
+                }
+            }
+        }
+        // Themes that extend another theme are resolved against each other and the registered themes
+        let results = resolve_themes(&sources, &self.themes);
+        for (path, result) in paths.into_iter().zip(results) {
+            match result {
+                Ok(document) => {
+                    log::debug!("Loaded theme {:?} from {:?}", document.name, path);
+                    self.next_id = self.next_id.max(document.id + 1);
+                    self.register_theme(document);
+                }
+                Err(err) => {
+                    log::error!("Could not load {:?}: {}", path, err);
+                    self.load_errors.push((path, err));
+                }
+            }
+        }
+        self.reload_stage();
+    }
+
+    /// Import all VS Code color themes from the vscode subdirectory and register them in the ThemePicker.
+    /// Dark themes use Night Owl for any colors the mapping cannot fill, and light themes use Light Owl.
+    /// Importing again replaces the themes imported from the same files, or with the same names, so
+    /// they are not listed twice.
+    fn import_vscode_themes(&mut self) {
+        let dir = self.themes_dir.join(VSCODE_DIR);
+        let mapping = self.vscode_mapping();
+        let files = match vscode_theme_files(&dir) {
+            Ok(files) => files,
+            Err(err) => {
+                log::error!("Could not read {:?}: {}", dir, err);
+                return;
+            }
+        };
+        for path in files {
+            match VscodeTheme::load(&path) {
+                Ok(vscode) => {
+                    let base = if vscode.is_dark() {
+                        ThemeDocument::from_theme(DARK_THEME, "Dark theme", &ThemeBuilder::night_owl())
+                    } else {
+                        ThemeDocument::from_theme(LIGHT_THEME, "Light theme", &ThemeBuilder::light_owl())
+                    };
+                    let id = match self.imported_theme_id(&path, &vscode.name) {
+                        Some(id) => id,
+                        None => self.next_theme_id(),
+                    };
+                    self.imported.insert(path.clone(), id);
+                    let import = vscode.to_document(id, &base, &mapping);
+                    if !import.unmapped.is_empty() {
+                        log::warn!("{:?} has no mapped colors for {:?}", vscode.name, import.unmapped);
+                    }
+                    self.register_theme(import.document);
+                }
+                Err(err) => log::error!("Could not import {:?}: {}", path, err),
+            }
+        }
+        self.reload_stage();
+    }
+
+    /// The id of a listed theme imported from a VS Code theme file before, or else a theme with the same
+    /// name. Built-in themes are never replaced.
+    fn imported_theme_id(&self, path: &Path, name: &str) -> Option<u32> {
+        let builtin: Vec<u32> = builtin_themes().iter().map(|theme| theme.id).collect();
+        let listed = |id: &u32| !builtin.contains(id) && self.themes.iter().any(|theme| theme.id == *id);
+        if let Some(id) = self.imported.get(path).cloned().filter(listed) {
+            return Some(id);
+        }
+        self.themes.iter().find(|theme| theme.name == name && listed(&theme.id)).map(|theme| theme.id)
+    }
+
+    /// Export the current theme to the vscode subdirectory. Since it uses the same mapping as
+    /// import_vscode_themes, the file can be imported again.
+    fn export_vscode_theme(&mut self) {
+        let dir = self.themes_dir.join(VSCODE_DIR);
+        if let Err(err) = fs::create_dir_all(&dir) {
+            log::error!("Could not create {:?}: {}", dir, err);
+            return;
+        }
+        let export = export_vscode_theme(&self.document, &self.vscode_mapping());
+        if !export.unfilled.is_empty() {
+            log::warn!("VS Code keys not filled by {:?}: {:?}", self.document.name, export.unfilled);
+        }
+        let path = dir.join(vscode_file_name(&self.document));
+        match export.save(&path) {
+            Ok(()) => log::debug!("Exported theme {:?} to {:?}", self.document.name, path),
+            Err(err) => log::error!("Could not export {:?}: {}", path, err),
+        }
+    }
+
+    /// Export the current theme as a CSS stylesheet and an SCSS file in the css subdirectory
+    fn export_css(&mut self) {
+        let dir = self.themes_dir.join(CSS_DIR);
+        if let Err(err) = fs::create_dir_all(&dir) {
+            log::error!("Could not create {:?}: {}", dir, err);
+            return;
+        }
+        let slug = self.document.slug();
+        let files = [
+            (dir.join(format!("{}.css", slug)), export_css(&self.document, &self.css_options)),
+            (dir.join(format!("_{}.scss", slug)), export_scss(&self.document, &self.css_options)),
+        ];
+        for (path, text) in files.iter() {
+            match fs::write(path, text) {
+                Ok(()) => log::debug!("Exported theme {:?} to {:?}", self.document.name, path),
+                Err(err) => log::error!("Could not export {:?}: {}", path, err),
+            }
+        }
+    }
+
+    /// Export the current theme as a Rust module in the rust subdirectory. The file is named after the
+    /// generated function, so it can be added to a crate as is. Eg: themes/rust/my_theme.rs
+    fn export_rust(&mut self) {
+        let dir = self.themes_dir.join(RUST_DIR);
+        if let Err(err) = fs::create_dir_all(&dir) {
+            log::error!("Could not create {:?}: {}", dir, err);
+            return;
+        }
+        let fn_name = rust_function_name(&self.document);
+        let path = dir.join(format!("{}.rs", fn_name));
+        match fs::write(&path, generate_rust_module(&self.document, &fn_name)) {
+            Ok(()) => log::debug!("Exported theme {:?} to {:?}", self.document.name, path),
+            Err(err) => log::error!("Could not export {:?}: {}", path, err),
+        }
+    }
+
+    /// The mapping file in the vscode subdirectory if there is one, else the default mapping
+    fn vscode_mapping(&self) -> VscodeMapping {
+        let path = self.themes_dir.join(VSCODE_DIR).join(VSCODE_MAPPING_FILE);
+        if !path.exists() {
+            return VscodeMapping::default();
+        }
+        VscodeMapping::load(&path).unwrap_or_else(|err| {
+            log::error!("Could not load {:?}, using default mapping: {}", path, err);
+            VscodeMapping::default()
+        })
+    }
+
+    /// Add a theme to the ThemePicker and make it the current theme. A theme with the same id replaces
+    /// the existing one.
+    fn register_theme(&mut self, document: ThemeDocument) {
+        self.add_to_picker(&document);
+        match self.themes.iter().position(|theme| theme.id == document.id) {
+            Some(index) => self.themes[index] = document.clone(),
+            None => self.themes.push(document.clone()),
+        }
+        self.set_current(document);
+    }
+
+    /// Make a document the one being edited
+    fn set_current(&mut self, document: ThemeDocument) {
+        self.document = document;
+        self.expression_errors.clear();
+        self.open_dropdown = None;
+        self.name_entry = None;
+        self.contrast = contrast_report(&self.document.to_theme());
+    }
+
+    /// Switch the editor to a registered theme, as selected in the Explorer list
+    fn select_theme(&mut self, id: u32) {
+        if id == self.document.id {
+            return;
+        }
+        if let Some(document) = self.themes.iter().find(|theme| theme.id == id).cloned() {
+            self.set_current(document);
+            self.reload_stage();
+        }
+    }
+
+    /// Rename the current theme to the typed name, as an edit that can be undone. Themes that extend it by
+    /// its slug are changed to extend it by id, so they keep their base theme under any name. The
+    /// ThemePicker entry is replaced, and the Explorer list shows the new name when the stage is rebuilt.
+    fn commit_rename(&mut self) {
+        let name = self.name_entry.take().map(|name| name.trim().to_string()).unwrap_or_default();
+        let old = self.document.clone();
+        if name.is_empty() || name == old.name {
+            self.reload_stage();
+            return;
+        }
+        let reference = old.id.to_string();
+        for theme in self.themes.iter_mut() {
+            if theme.extends.as_ref().map_or(false, |r| *r != reference && old.matches_ref(r)) {
+                theme.extends = Some(reference.clone());
+                self.dirty.insert(theme.id);
+            }
+        }
+        let path = self.themes_dir.join(old.file_name());
+        if path.is_file() {
+            self.renamed_files.entry(old.id).or_insert(path);
+        }
+        self.set_field_value(NAME_FIELD, FieldValue::Text(name));
+    }
+
+    /// Change a field of the current theme. Colors derived from it by expressions are evaluated
+    /// again and the Main scene preview is updated. Returns false if the value does not fit the field
+    /// or is the current value, in which case the stage is not rebuilt.
+    pub fn set_field_value(&mut self, path: &str, value: FieldValue) -> bool {
+        if !self.document.override_field(path, value) {
+            return false;
+        }
+        let result = self.document.apply_expressions();
+        self.document_changed(result);
+        true
+    }
+
+    /// Derive a color of the current theme from an expression. Eg: "darken(fg_color, 10%)"
+    pub fn set_color_expression(&mut self, field: &str, text: &str) -> bool {
+        let result = self.document.set_expression(field, text);
+        let ok = result.is_ok();
+        self.document_changed(result);
+        ok
+    }
+
+    /// Store the edited document as an Edit command in the history, replace its ThemePicker entry, update
+    /// the themes that extend it and rebuild the stage, since the inspector, warnings and the styled preview
+    /// controls all show values of the document. The theme is only marked as modified if the document
+    /// differs from the stored one.
+    fn document_changed(&mut self, result: Result<(), Vec<ExprError>>) {
+        self.expression_errors = result.err().unwrap_or_default();
+        if let Some(index) = self.themes.iter().position(|theme| theme.id == self.document.id) {
+            if self.themes[index] != self.document {
+                let before = std::mem::replace(&mut self.themes[index], self.document.clone());
+                self.dirty.insert(self.document.id);
+                let command = ThemeCommand::edit(before, self.document.clone());
+                self.history.record(command, self.frames);
+                let document = self.document.clone();
+                self.add_to_picker(&document);
+            }
+        }
+        self.rebase_dependents();
+        self.contrast = contrast_report(&self.document.to_theme());
+        self.reload_stage();
+    }
+
+    /// Update the themes that extend the current theme with its values
+    fn rebase_dependents(&mut self) {
+        let document = &self.document;
+        let extends_document =
+            |theme: &&mut ThemeDocument| theme.extends.as_ref().map_or(false, |r| document.matches_ref(r));
+        for theme in self.themes.iter_mut().filter(extends_document) {
+            if let Err(errors) = theme.rebase(document) {
+                log::warn!("Expression problems in {:?} after rebase: {:?}", theme.name, errors);
+            }
+        }
+    }
+
+    /// Apply a command to the themes and record it in the history
+    fn execute(&mut self, command: ThemeCommand) {
+        if let Err(err) = command.apply(&mut self.themes) {
+            log::error!("{}: {}", command.title(), err);
+        }
+        self.command_done(&command);
+        self.history.record(command, self.frames);
+    }
+
+    /// Revert the last command in the history
+    fn undo_command(&mut self) {
+        if let Some(command) = self.history.undo() {
+            if let Err(err) = command.revert(&mut self.themes) {
+                log::error!("Undo {}: {}", command.title(), err);
+            }
+            self.command_done(&command);
+        }
+    }
+
+    /// Apply the last undone command again
+    fn redo_command(&mut self) {
+        if let Some(command) = self.history.redo() {
+            if let Err(err) = command.apply(&mut self.themes) {
+                log::error!("Redo {}: {}", command.title(), err);
+            }
+            self.command_done(&command);
+        }
+    }
+
+    /// Show the themes after a command was applied or reverted. The changed theme becomes the current
+    /// theme, or if it was removed, the theme that took its place in the list. The ThemePicker has no way
+    /// to remove a theme, so the entry of a removed theme stays registered but is no longer listed.
+    fn command_done(&mut self, command: &ThemeCommand) {
+        let id = command.theme_id();
+        match self.themes.iter().find(|theme| theme.id == id).cloned() {
+            Some(document) => {
+                self.add_to_picker(&document);
+                // A removed theme that had a file was saved to it again when the removal was reverted
+                let saved = match command {
+                    ThemeCommand::Remove { file, .. } => file.is_some(),
+                    _ => false,
+                };
+                if !saved {
+                    self.dirty.insert(id);
+                }
+                self.set_current(document);
+                self.rebase_dependents();
+            }
+            None => {
+                self.dirty.remove(&id);
+                if self.document.id == id {
+                    let index = command.index().unwrap_or(0).min(self.themes.len().saturating_sub(1));
+                    if let Some(next) = self.themes.get(index).cloned() {
+                        self.set_current(next);
+                    }
+                }
+            }
+        }
+        self.reload_stage();
+    }
+
+    /// Set or clear a state color of the current theme. A derived theme then overrides the states of its
+    /// base theme as a whole.
+    fn set_state_color(&mut self, state: ControlState, field: &str, color: Option<Color>) {
+        let changed = match self.document.states.get_mut(state) {
+            Some(colors) => colors.get(field) != color && colors.set(field, color),
+            None => false,
+        };
+        if changed {
+            if self.document.extends.is_some() {
+                self.document.overrides.insert(STATES_FIELD.to_string());
+            }
+            self.document_changed(Ok(()));
+        }
+    }
+
+    /// True if the current theme has changes that have not been saved
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.contains(&self.document.id)
+    }
+
+    /// Add to a numeric field of the current theme. The font size stays at least MIN_FONT_SIZE and
+    /// the border width at least zero.
+    fn step_field(&mut self, path: &str, amount: f32) {
+        if let Some(FieldValue::Number(n)) = self.document.field(path) {
+            let min = if path == "font_size" { MIN_FONT_SIZE } else { 0.0 };
+            self.set_field_value(path, FieldValue::Number((n + amount).max(min)));
+        }
+    }
+
+    /// The values offered for a field in the property inspector
+    fn field_options(&self, path: &str) -> Vec<FieldValue> {
+        match path {
+            "fonts.default" | "fonts.title" => {
+                self.font_files.iter().map(|file| FieldValue::Text(file.clone())).collect()
+            }
+            "border.style" => vec![FieldValue::Border(BorderKind::None), FieldValue::Border(BorderKind::Solid)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Add a theme created from the "+" menu. A duplicate keeps the base theme of the current theme.
+    fn create_theme(&mut self, kind: NewThemeKind) {
+        self.create_menu = false;
+        let id = self.next_theme_id();
+        let document = match kind {
+            NewThemeKind::Blank => ThemeDocument::from_theme(id, "Untitled", &Theme::default()),
+            NewThemeKind::Duplicate => {
+                let name = format!("{} copy", self.document.name);
+                ThemeDocument { id, name, ..self.document.clone() }
+            }
+            NewThemeKind::Template(base_id) => {
+                let base = match self.themes.iter().find(|theme| theme.id == base_id) {
+                    Some(base) => base.clone(),
+                    None => return,
+                };
+                let derived = DerivedTheme {
+                    id,
+                    name: format!("{} variant", base.name),
+                    extends: base.slug(),
+                    values: BTreeMap::new(),
+                    expressions: BTreeMap::new(),
+                    states: None,
+                };
+                match derived.apply(&base) {
+                    Ok(document) => document,
+                    Err(err) => {
+                        log::error!("Could not create a theme from {:?}: {}", base.name, err);
+                        return;
+                    }
+                }
+            }
+        };
+        self.execute(ThemeCommand::Add { index: self.themes.len(), document });
+    }
+
+    /// Ask for confirmation before deleting the current theme. Built-in themes and themes that other
+    /// themes extend cannot be deleted.
+    fn delete_theme(&mut self) {
+        let document = &self.document;
+        if builtin_themes().iter().any(|theme| theme.id == document.id) {
+            log::warn!("The built-in theme {:?} cannot be deleted", document.name);
+            return;
+        }
+        let dependents: Vec<&str> = self
+            .themes
+            .iter()
+            .filter(|theme| theme.extends.as_ref().map_or(false, |r| document.matches_ref(r)))
+            .map(|theme| theme.name.as_str())
+            .collect();
+        if !dependents.is_empty() {
+            log::warn!("{:?} cannot be deleted because {:?} extend it", document.name, dependents);
+            return;
+        }
+        let title = format!("Delete \"{}\"?", document.name);
+        let message = "The theme and its saved file will be removed. You can undo this from the Explorer.";
+        let dialog = ConfirmController::new(self.frame.clone(), &title, message, self.actions.clone())
+            .with_choice("Cancel", None)
+            .with_choice("Delete", Some(EditorAction::ConfirmDelete(document.id)));
+        self.modal = Some(Box::new(dialog));
+    }
+
+    /// Remove a theme and its saved file with a command, so the deletion can be undone. The next theme in
+    /// the list becomes the current theme.
+    fn confirm_delete(&mut self, id: u32) {
+        let index = match self.themes.iter().position(|theme| theme.id == id) {
+            Some(index) => index,
+            None => return,
+        };
+        let document = self.themes[index].clone();
+        // A theme renamed since it was saved still has its file under the old name
+        let path = match self.renamed_files.get(&id) {
+            Some(path) => path.clone(),
+            None => self.themes_dir.join(document.file_name()),
+        };
+        let file = if path.is_file() { Some(path) } else { None };
+        self.execute(ThemeCommand::Remove { index, document, file });
+    }
+
+    /// Open the "New theme from color…" form, seeded with the accent of the current theme, or close it
+    fn toggle_palette_form(&mut self) {
+        self.palette_form = match self.palette_form {
+            Some(_) => None,
+            None => {
+                let bg = &self.document.colors.bg_color;
+                let dark = contrast_ratio(&Color::WHITE, bg) > contrast_ratio(&Color::BLACK, bg);
+                let options = PaletteOptions::default().with_dark(dark);
+                Some(PaletteForm { seed: self.document.colors.button_bg_color, second: None, options })
+            }
+        };
+        self.reload_stage();
+    }
+
+    /// Change the open palette form and preview the result
+    fn update_palette_form<F: FnOnce(&mut PaletteForm)>(&mut self, change: F) {
+        if let Some(form) = &mut self.palette_form {
+            change(form);
+            self.reload_stage();
+        }
+    }
+
+    /// Add the theme generated by the palette form and close the form
+    fn create_from_palette(&mut self) {
+        let form = match self.palette_form.take() {
+            Some(form) => form,
+            None => return,
+        };
+        let id = self.next_theme_id();
+        let document = form.palette().to_document(id, &format!("From {}", hex_string(&form.seed)));
+        self.execute(ThemeCommand::Add { index: self.themes.len(), document });
+    }
+
+    /// Apply a color picker change to the picked field of the current theme. A changed field rebuilds
+    /// the stage through document_changed, so it is only rebuilt here to show the picker itself.
+    fn picker_action(&mut self, action: PickerAction) {
+        if self.color_picker.apply(action) {
+            let path = format!("{}{}", COLOR_FIELD_PREFIX, self.color_picker.field);
+            if self.set_field_value(&path, FieldValue::Color(self.color_picker.color())) {
+                return;
+            }
+        }
+        self.reload_stage();
+    }
+
+    /// Type into the theme name or the color picker's text entry, if one is being edited
+    pub fn type_text(&mut self, text: &str) {
+        if let Some(entry) = &mut self.name_entry {
+            entry.push_str(text);
+            self.reload_stage();
+        } else if self.color_picker.entry().is_some() {
+            self.color_picker.type_text(text);
+            self.reload_stage();
+        }
+    }
+
+    /// Remove the last character of the theme name or the color picker's text entry
+    pub fn backspace(&mut self) {
+        if let Some(entry) = &mut self.name_entry {
+            entry.pop();
+            self.reload_stage();
+        } else if self.color_picker.entry().is_some() {
+            self.color_picker.backspace();
+            self.reload_stage();
+        }
+    }
+
+    fn next_theme_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Save the current theme as a file in the themes directory
+    fn save_theme(&mut self) {
+        let document = self.document.clone();
+        if self.save_document(&document) {
+            self.reload_stage();
+        }
+    }
+
+    /// Save a theme as a file in the themes directory and mark it as saved. Returns false if it failed.
+    fn save_document(&mut self, document: &ThemeDocument) -> bool {
+        if let Err(err) = fs::create_dir_all(&self.themes_dir) {
+            log::error!("Could not create {:?}: {}", self.themes_dir, err);
+            return false;
+        }
+        let path = self.themes_dir.join(document.file_name());
+        match document.save(&path) {
+            Ok(()) => {
+                log::debug!("Saved theme {:?} to {:?}", document.name, path);
+                self.dirty.remove(&document.id);
+                if let Some(old) = self.renamed_files.remove(&document.id) {
+                    if old != path {
+                        if let Err(err) = fs::remove_file(&old) {
+                            log::warn!("Could not remove {:?} after renaming: {}", old, err);
+                        }
+                    }
+                }
+                true
+            }
+            Err(err) => {
+                log::error!("Could not save {:?}: {}", path, err);
+                false
+            }
+        }
+    }
+
+    /// The themes with changes that have not been saved, in list order
+    fn unsaved_themes(&self) -> Vec<ThemeDocument> {
+        self.themes.iter().filter(|theme| self.dirty.contains(&theme.id)).cloned().collect()
+    }
+
+    /// Quit the app, saving the unsaved themes first if asked to. If a theme cannot be saved, the app
+    /// stays open so the changes are not lost.
+    fn quit(&mut self, save: bool, window: &mut Window) {
+        if save {
+            let failed = self.unsaved_themes().iter().filter(|theme| !self.save_document(theme)).count();
+            if failed > 0 {
+                log::error!("{} themes could not be saved, not quitting", failed);
+                self.reload_stage();
+                return;
+            }
+        }
+        self.remove_journal();
+        window.close();
+    }
+
+    fn journal_path(&self) -> PathBuf {
+        self.themes_dir.join(RECOVERY_FILE_NAME)
+    }
+
+    /// Write the unsaved themes to the recovery journal if they changed since the last autosave, or
+    /// remove the journal if everything is saved
+    fn write_journal(&mut self) {
+        let unsaved = self.unsaved_themes();
+        if unsaved.is_empty() {
+            if self.journal.is_some() {
+                self.remove_journal();
+            }
+            return;
+        }
+        let journal = match RecoveryJournal::new(&unsaved) {
+            Ok(journal) => journal,
+            Err(err) => {
+                log::error!("Could not journal unsaved themes: {}", err);
+                return;
+            }
+        };
+        if self.journal.as_ref() == Some(&journal) {
+            return;
+        }
+        if let Err(err) = fs::create_dir_all(&self.themes_dir) {
+            log::error!("Could not create {:?}: {}", self.themes_dir, err);
+            return;
+        }
+        let path = self.journal_path();
+        match journal.save(&path) {
+            Ok(()) => {
+                log::debug!("Journaled {} unsaved themes to {:?}", unsaved.len(), path);
+                self.journal = Some(journal);
+            }
+            Err(err) => log::error!("Could not write {:?}: {}", path, err),
+        }
+    }
+
+    /// Remove the recovery journal, when there is nothing left to recover
+    fn remove_journal(&mut self) {
+        let path = self.journal_path();
+        if let Err(err) = RecoveryJournal::remove(&path) {
+            log::error!("Could not remove {:?}: {}", path, err);
+        }
+        self.journal = None;
+    }
+
+    /// Register the themes of the journal found at launch, as unsaved themes, or remove the journal
+    fn recover(&mut self, restore: bool) {
+        let journal = match self.recovery.take() {
+            Some(journal) => journal,
+            None => return,
+        };
+        if !restore {
+            self.remove_journal();
+            return;
+        }
+        for result in journal.restore(&self.themes) {
+            match result {
+                Ok(document) => {
+                    log::debug!("Recovered theme {:?}", document.name);
+                    self.next_id = self.next_id.max(document.id + 1);
+                    self.dirty.insert(document.id);
+                    self.register_theme(document);
+                }
+                Err(err) => log::error!("Could not recover a theme: {}", err),
+            }
+        }
+        self.journal = Some(journal);
+        self.reload_stage();
+    }
+
+    /// Rebuild all scenes to reflect changes in the editor state
+    fn reload_stage(&mut self) {
+        // Show the current value of the picked field, which may have changed by other means
+        if let Some(color) = self.document.colors.get(self.color_picker.field) {
+            self.color_picker.set_color(color);
+        }
+        self.stage = self.build_stage(self.frame.clone());
+        self.stage.notify(&DisplayEvent::Ready);
+        self.apply_document();
+    }
+
+    /// The current theme, or the theme generated by the open palette form, through the preview filter
+    fn preview_document(&self) -> ThemeDocument {
+        let document = match &self.palette_form {
+            Some(form) => form.palette().to_document(self.document.id, &self.document.name),
+            None => self.document.clone(),
+        };
+        self.filtered(document)
+    }
+
+    /// A document as seen through the preview filter
+    fn filtered(&self, document: ThemeDocument) -> ThemeDocument {
+        match self.preview_filter {
+            Some(kind) => document.simulate_cvd(kind),
+            None => document,
+        }
+    }
+
+    /// In compare mode, the theme shown next to the current theme. None if the theme was deleted.
+    fn compare_document(&self) -> Option<&ThemeDocument> {
+        let id = self.compare_id?;
+        self.themes.iter().find(|theme| theme.id == id)
+    }
+
+    /// Apply the preview theme to the stage
+    fn apply_document(&mut self) {
+        let document = self.preview_document();
+        let mut theme = match self.font_data(&document.fonts) {
+            Some(fonts) => document.to_theme_with_fonts(&fonts),
+            None => document.to_theme(),
+        };
+        self.stage.set_theme(&mut theme);
+    }
+
+    /// The fonts of a theme from FONTS_DIR. Each pair of files is read once. If they cannot be read, eg:
+    /// on the web, the problem is logged once and None is returned, so the theme uses the default fonts.
+    fn font_data(&mut self, fonts: &ThemeFonts) -> Option<FontData> {
+        let key = (fonts.default.clone(), fonts.title.clone());
+        let data = self.font_cache.entry(key).or_insert_with(|| match fonts.load(FONTS_DIR) {
+            Ok(data) => Some(data),
+            Err(err) => {
+                log::warn!("Could not load the fonts {:?} and {:?}: {}", fonts.default, fonts.title, err);
+                None
+            }
+        });
+        data.clone()
+    }
+
+    /// Add a theme to the ThemePicker, drawn with its fonts, or replace the entry with the same id
+    fn add_to_picker(&mut self, document: &ThemeDocument) {
+        let source = document.clone();
+        let fonts = self.font_data(&document.fonts);
+        self.theme_picker.add_theme(document.id, &document.name, move || match &fonts {
+            Some(fonts) => source.to_theme_with_fonts(fonts),
+            None => source.to_theme(),
+        });
+    }
+
+    /// Create a button that queues the given action when clicked. It can also take keyboard focus.
+    fn action_button(&self, frame: Rectangle, title: &str, action: EditorAction) -> Button {
+        self.focus.borrow_mut().add(frame.clone(), title, action.clone());
+        let mut button = Button::new(frame).with_text(title);
+        let actions = self.actions.clone();
+        button.set_onclick(move |_state: &mut AppState| {
+            actions.borrow_mut().push(action.clone());
+        });
+        button
+    }
+
+    /// Create a button filled with a color, with a thicker border if it is selected
+    fn swatch_button(&self, frame: Rectangle, color: Color, selected: bool, action: EditorAction) -> Button {
+        let mut button = self.action_button(frame, "", action);
+        button.layer.bg_style = BackgroundStyle::Solid(color);
+        button.layer.border_style = if selected {
+            BorderStyle::SolidLine(Color::BLACK, 3.0)
+        } else {
+            BorderStyle::SolidLine(Color::from_hex("#CCCCCC"), 1.0)
+        };
+        button.layer.lock_style = true;
+        button
+    }
+
     fn build_stage(&mut self, frame: Rectangle) -> Stage {
         let mut stage = Stage::new(frame.clone());
         stage.title = "Theme Builder".to_string();
@@ -61,6 +1046,7 @@ impl ThemeEditor {
         let col1 = Rectangle::new((0.0, 0.0), (COL1_WIDTH, frame.height()));
         let col2 = Rectangle::new((COL1_WIDTH, 0.0), (COL2_WIDTH, frame.height()));
         let col3 = Rectangle::new((COL1_WIDTH + COL2_WIDTH, 0.0), (COL3_WIDTH, frame.height()));
+        self.focus.get_mut().reset(vec![col1.x(), col2.x(), col3.x()]);
 
         // Make Scene for Column 1
         let scene = self.explorer_scene(&col1);
@@ -71,8 +1057,7 @@ impl ThemeEditor {
         stage.add_scene(scene);
 
         // Make Scene for Column 3
-        let mut scene = Scene::new(col3).with_id(3, "Properties");
-        scene.layer.border_style = BorderStyle::SolidLine(Color::from_hex("#000000"), 1.0);
+        let scene = self.properties_scene(&col3);
         stage.add_scene(scene);
 
         stage
@@ -81,7 +1066,7 @@ impl ThemeEditor {
     /// Define the first column layout
     /// V:|-[themes title]-|-[listbox]-|-
     ///     H:|-[add button]-|-[remove button]-|
-    /// -|-[empty space]-|
+    /// -|-[import/export menu]-|-[create menu]-|-[undo delete]-|-[compare]-|-[empty space]-|
     ///
     /// See: https://vislyhq.github.io/stretch/docs/rust/
     fn explorer_scene(&mut self, frame: &Rectangle) -> Scene {
@@ -96,104 +1081,575 @@ impl ThemeEditor {
         text.text_align(TextAlign::Center);
         scene.add_control(Box::new(text));
 
-        // Add listbox
+        // Add listbox with one row per registered theme. The current theme is marked with a bullet
+        // and themes with unsaved changes with an asterisk.
         let subframe = scene.sub_frame((0.0, 50.0), (200.0, 200.0));
         let mut listbox = ListBox::new(subframe);
         listbox.row_border_style = BorderStyle::SolidLine(Color::from_hex("#EEEEEE"), 1.0);
 
-        let numbers: Vec<u32> = (0..21).collect();
-        let ds: Vec<String> = numbers.into_iter().map(|x| x.to_string()).collect();
+        let ds: Vec<String> = self
+            .themes
+            .iter()
+            .map(|theme| {
+                let marker = if theme.id == self.document.id { "• " } else { "" };
+                let dirty = if self.dirty.contains(&theme.id) { " *" } else { "" };
+                format!("{}{}{}", marker, theme.name, dirty)
+            })
+            .collect();
         listbox.set_datasource(ds);
+        let ids: Vec<u32> = self.themes.iter().map(|theme| theme.id).collect();
+        let actions = self.actions.clone();
+        listbox.set_onclick(move |_state: &mut AppState, row: usize| {
+            if let Some(id) = ids.get(row) {
+                actions.borrow_mut().push(EditorAction::SelectTheme(*id));
+            }
+        });
         scene.add_control(Box::new(listbox));
 
         // Toolbar buttons to add/remove
         let subframe = scene.sub_frame((0.0, 250.0), (MINI_BUTTON_SIZE, MINI_BUTTON_SIZE));
-        let mut button = Button::new(subframe).with_text("+");
+        let mut button = self.action_button(subframe, "+", EditorAction::ToggleCreateMenu);
         button.layer.font_style = FontStyle::new(20.0, Color::BLACK);
         button.layer.lock_style = true;
         button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 1.0);
         scene.add_control(Box::new(button));
 
         let subframe = scene.sub_frame((MINI_BUTTON_SIZE, 250.0), (MINI_BUTTON_SIZE, MINI_BUTTON_SIZE));
-        let mut button = Button::new(subframe).with_text("–");
+        let mut button = self.action_button(subframe, "–", EditorAction::DeleteTheme);
         button.layer.font_style = FontStyle::new(20.0, Color::BLACK);
         button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 1.0);
         button.layer.lock_style = true;
         scene.add_control(Box::new(button));
 
+        // File buttons to open and save themes
+        let file_x = frame.width() - FILE_BUTTON_WIDTH * 2.0;
+        let subframe = scene.sub_frame((file_x, 250.0), (FILE_BUTTON_WIDTH, MINI_BUTTON_SIZE));
+        let mut button = self.action_button(subframe, "Open", EditorAction::OpenThemes);
+        button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 1.0);
+        scene.add_control(Box::new(button));
+
+        let subframe = scene.sub_frame((file_x + FILE_BUTTON_WIDTH, 250.0), (FILE_BUTTON_WIDTH, MINI_BUTTON_SIZE));
+        let mut button = self.action_button(subframe, "Save", EditorAction::SaveTheme);
+        button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 1.0);
+        scene.add_control(Box::new(button));
+
+        // Import and export menu, one full width button per row below the file buttons
+        let menu: &[(&str, EditorAction)] = &[
+            ("Import VS Code", EditorAction::ImportVscode),
+            ("Export VS Code", EditorAction::ExportVscode),
+            ("Export CSS", EditorAction::ExportCss),
+            ("Export Rust", EditorAction::ExportRust),
+        ];
+        for (i, (title, action)) in menu.iter().enumerate() {
+            let ypos = 250.0 + MINI_BUTTON_SIZE * (i + 1) as f32;
+            let subframe = scene.sub_frame((file_x, ypos), (FILE_BUTTON_WIDTH * 2.0, MINI_BUTTON_SIZE));
+            let mut button = self.action_button(subframe, title, action.clone());
+            button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 1.0);
+            scene.add_control(Box::new(button));
+        }
+
+        // The "+" menu and the undo button, one full width row each below the import and export menu
+        let mut rows: Vec<(String, EditorAction)> = Vec::new();
+        if self.create_menu {
+            rows.push(("Blank theme".to_string(), EditorAction::CreateTheme(NewThemeKind::Blank)));
+            let duplicate = format!("Duplicate {}", self.document.name);
+            rows.push((duplicate, EditorAction::CreateTheme(NewThemeKind::Duplicate)));
+            for template in builtin_themes() {
+                let title = format!("From {}", template.name);
+                rows.push((title, EditorAction::CreateTheme(NewThemeKind::Template(template.id))));
+            }
+            rows.push(("From color…".to_string(), EditorAction::NewTheme));
+        }
+        if let Some(ThemeCommand::Remove { document, .. }) = self.history.next_undo() {
+            rows.push((format!("Undo delete {}", document.name), EditorAction::Undo));
+        }
+        // Compare pins the current theme, so that selecting another theme shows the two side by side
+        match self.compare_document() {
+            Some(_) => rows.push(("End compare".to_string(), EditorAction::Compare(None))),
+            None => rows.push(("Compare…".to_string(), EditorAction::Compare(Some(self.document.id)))),
+        }
+        let menu_ypos = 250.0 + MINI_BUTTON_SIZE * (menu.len() + 1) as f32 + 10.0;
+        for (i, (title, action)) in rows.into_iter().enumerate() {
+            let ypos = menu_ypos + MINI_BUTTON_SIZE * i as f32;
+            let subframe = scene.sub_frame((0.0, ypos), (frame.width(), MINI_BUTTON_SIZE));
+            let mut button = self.action_button(subframe, &title, action);
+            button.layer.border_style = BorderStyle::SolidLine(Color::from_hex("#CCCCCC"), 1.0);
+            scene.add_control(Box::new(button));
+        }
+
         scene
     }
 
+    /// Define the third column layout. The color picker for one color field of the current theme is
+    /// at the top. Below it, problems from the last Open are listed per file, with the JSON path and
+    /// position of each field above its error message. Then the color pairs of the current theme
+    /// below WCAG AA contrast, and its color expressions and their problems. If the current theme
+    /// extends another theme, every field is listed and marked as inherited or overridden.
+    /// While the "New theme from color…" form is open, the scene shows only the form.
+    /// V:|-[picker]-|-[file name]-|-[field path]-|-[message]-|-...-|-[warning]-|-...-|-[expression]-|-...
+    /// -|-[theme name]-|-[inspector]-|
+    fn properties_scene(&self, frame: &Rectangle) -> Scene {
+        let mut scene = Scene::new(frame.clone()).with_id(3, "Properties");
+        scene.layer.border_style = BorderStyle::SolidLine(Color::from_hex("#000000"), 1.0);
+
+        let mut ypos = 0.0;
+        let add_text = |scene: &mut Scene, ypos: &mut f32, value: &str, color: Color| {
+            let subframe = scene.sub_frame((5.0, *ypos), (frame.width() - 10.0, PROPERTY_ROW_HEIGHT));
+            let mut text = Text::new(subframe, value);
+            text.layer.font_style = FontStyle::new(12.0, color);
+            text.text_align(TextAlign::Left);
+            scene.add_control(Box::new(text));
+            *ypos += PROPERTY_ROW_HEIGHT;
+        };
+        if let Some(form) = &self.palette_form {
+            self.add_palette_form(&mut scene, frame, form, ypos);
+            return scene;
+        }
+
+        let picker_frame = Rectangle::new((5.0, ypos), (frame.width() - 10.0, frame.height() - ypos));
+        ypos += self.color_picker.add_controls(&mut scene, &picker_frame, |frame, title, action| {
+            self.action_button(frame, title, action)
+        });
+
+        for (path, error) in &self.load_errors {
+            let file_name = path.file_name().map_or(String::new(), |name| name.to_string_lossy().to_string());
+            add_text(&mut scene, &mut ypos, &file_name, Color::BLACK);
+            match error {
+                ThemeError::Validation(errors) => {
+                    for error in errors {
+                        let field = format!("{} ({}:{})", error.path, error.line, error.column);
+                        add_text(&mut scene, &mut ypos, &field, Color::from_hex("#666666"));
+                        add_text(&mut scene, &mut ypos, &error.message, Color::RED);
+                    }
+                }
+                _ => add_text(&mut scene, &mut ypos, &error.to_string(), Color::RED),
+            }
+        }
+
+        let warnings = self.contrast.warnings(ContrastGrade::Aa);
+        if !warnings.is_empty() {
+            add_text(&mut scene, &mut ypos, "Contrast", Color::BLACK);
+            for warning in &warnings {
+                add_text(&mut scene, &mut ypos, warning, Color::from_hex("#CC6600"));
+            }
+        }
+
+        if !self.document.expressions.is_empty() {
+            add_text(&mut scene, &mut ypos, "Expressions", Color::BLACK);
+            for (field, text) in &self.document.expressions {
+                add_text(&mut scene, &mut ypos, &format!("{} = {}", field, text), Color::from_hex("#666666"));
+            }
+            for error in &self.expression_errors {
+                add_text(&mut scene, &mut ypos, &error.to_string(), Color::RED);
+            }
+        }
+
+        let mut title = self.document.name.clone();
+        if self.is_dirty() {
+            title.push_str(" (modified)");
+        }
+        add_text(&mut scene, &mut ypos, &title, Color::BLACK);
+        if let Some(base) = &self.document.extends {
+            add_text(&mut scene, &mut ypos, &format!("Extends: {}", base), Color::from_hex("#666666"));
+        }
+        if let Some(other) = self.compare_document() {
+            let count = self.document.differing_fields(other).len();
+            let line = format!("Compared with {}: {} field(s) differ", other.name, count);
+            add_text(&mut scene, &mut ypos, &line, COMPARE_COLOR);
+        }
+        self.add_inspector(&mut scene, frame, ypos);
+
+        scene
+    }
+
+    /// Add a row for the name and every field of the current theme, grouped by FieldGroup. Clicking the name
+    /// starts typing a new one, with Apply and Cancel buttons below it. Colors have a swatch that
+    /// selects the field in the color picker, numbers have -/+ steppers and fonts and the border style
+    /// have a dropdown of options. Inherited fields have gray labels. In compare mode, fields that differ
+    /// from the compared theme are marked with ≠ and their labels are highlighted.
+    /// V:|-[name]-|-[group]-|-[label | editor]-|-...-|-[group]-|-...
+    fn add_inspector(&self, scene: &mut Scene, frame: &Rectangle, mut ypos: f32) {
+        let differing = self.compare_document().map_or(Vec::new(), |other| self.document.differing_fields(other));
+        let width = frame.width() - 10.0;
+        let editor_x = 5.0 + INSPECTOR_LABEL_WIDTH;
+        let editor_width = width - INSPECTOR_LABEL_WIDTH;
+        let add_label = |scene: &mut Scene, origin: (f32, f32), width: f32, value: &str, color: Color| {
+            let subframe = scene.sub_frame(origin, (width, PROPERTY_ROW_HEIGHT));
+            let mut text = Text::new(subframe, value);
+            text.layer.font_style = FontStyle::new(12.0, color);
+            text.text_align(TextAlign::Left);
+            scene.add_control(Box::new(text));
+        };
+        let add_button = |scene: &mut Scene, origin: (f32, f32), width: f32, title: &str, action: EditorAction| {
+            let subframe = scene.sub_frame(origin, (width, PROPERTY_ROW_HEIGHT));
+            let mut button = self.action_button(subframe, title, action);
+            button.layer.font_style = FontStyle::new(12.0, Color::BLACK);
+            button.layer.border_style = BorderStyle::SolidLine(Color::from_hex("#CCCCCC"), 1.0);
+            scene.add_control(Box::new(button));
+        };
+
+        ypos += 5.0;
+        let title = match &self.name_entry {
+            Some(text) => format!("{}|", text),
+            None => self.document.name.clone(),
+        };
+        add_label(scene, (5.0, ypos), INSPECTOR_LABEL_WIDTH, NAME_FIELD, Color::BLACK);
+        add_button(scene, (editor_x, ypos), editor_width, &title, EditorAction::BeginRename);
+        ypos += PROPERTY_ROW_HEIGHT;
+        if self.name_entry.is_some() {
+            let half = editor_width / 2.0;
+            add_button(scene, (editor_x, ypos), half, "Apply", EditorAction::CommitRename);
+            add_button(scene, (editor_x + half, ypos), half, "Cancel", EditorAction::CancelRename);
+            ypos += PROPERTY_ROW_HEIGHT;
+        }
+
+        for group in FieldGroup::ALL.iter() {
+            ypos += 5.0;
+            add_label(scene, (5.0, ypos), width, &group.to_string(), Color::BLACK);
+            ypos += PROPERTY_ROW_HEIGHT;
+            for path in group.fields() {
+                let name = path.rsplit('.').next().unwrap_or(path);
+                if differing.contains(&path) {
+                    add_label(scene, (5.0, ypos), INSPECTOR_LABEL_WIDTH, &format!("≠ {}", name), COMPARE_COLOR);
+                } else {
+                    let label_color =
+                        if self.document.is_inherited(path) { Color::from_hex("#999999") } else { Color::BLACK };
+                    add_label(scene, (5.0, ypos), INSPECTOR_LABEL_WIDTH, name, label_color);
+                }
+                match self.document.field(path) {
+                    Some(FieldValue::Color(color)) => {
+                        let field = &path[COLOR_FIELD_PREFIX.len()..];
+                        let subframe = scene.sub_frame((editor_x, ypos), (PROPERTY_ROW_HEIGHT, PROPERTY_ROW_HEIGHT));
+                        let selected = field == self.color_picker.field;
+                        let action = EditorAction::Picker(PickerAction::SelectField(field));
+                        scene.add_control(Box::new(self.swatch_button(subframe, color, selected, action)));
+                        let value = match self.document.expressions.get(field) {
+                            Some(expression) => format!("= {}", expression),
+                            None => hex_string(&color),
+                        };
+                        let origin = (editor_x + PROPERTY_ROW_HEIGHT + 5.0, ypos);
+                        add_label(scene, origin, editor_width - PROPERTY_ROW_HEIGHT - 5.0, &value, Color::BLACK);
+                    }
+                    Some(FieldValue::Number(n)) => {
+                        let step = if path == "font_size" { 1.0 } else { 0.5 };
+                        let size = PROPERTY_ROW_HEIGHT;
+                        add_button(scene, (editor_x, ypos), size, "-", EditorAction::StepField(path, -step));
+                        let origin = (editor_x + size + 5.0, ypos);
+                        add_label(scene, origin, editor_width - size * 2.0 - 10.0, &n.to_string(), Color::BLACK);
+                        let origin = (editor_x + editor_width - PROPERTY_ROW_HEIGHT, ypos);
+                        add_button(scene, origin, PROPERTY_ROW_HEIGHT, "+", EditorAction::StepField(path, step));
+                    }
+                    Some(value) => {
+                        let title = value.to_string();
+                        add_button(scene, (editor_x, ypos), editor_width, &title, EditorAction::ToggleDropdown(path));
+                        if self.open_dropdown == Some(path) {
+                            // The options are listed below the field, with the current value marked
+                            for option in self.field_options(path) {
+                                ypos += PROPERTY_ROW_HEIGHT;
+                                let title = if option == value { format!("• {}", option) } else { option.to_string() };
+                                let action = EditorAction::SetField(path, option);
+                                add_button(scene, (editor_x, ypos), editor_width, &title, action);
+                            }
+                        }
+                    }
+                    None => (),
+                }
+                ypos += PROPERTY_ROW_HEIGHT;
+            }
+        }
+
+        // The colors of the previewed state. Clicking a swatch sets it to the color picker color.
+        let state = self.preview_state;
+        let overrides = match self.document.states.get(state) {
+            Some(overrides) => overrides,
+            None => return,
+        };
+        let style = self.document.state_style(state);
+        ypos += 5.0;
+        add_label(scene, (5.0, ypos), width, &format!("{} state", state), Color::BLACK);
+        ypos += PROPERTY_ROW_HEIGHT;
+        let resolved = [style.bg_color, style.fg_color, style.border_color];
+        for (field, color) in StateColors::FIELDS.iter().zip(resolved.iter()) {
+            let overridden = overrides.get(field).is_some();
+            let label_color = if overridden { Color::BLACK } else { Color::from_hex("#999999") };
+            add_label(scene, (5.0, ypos), INSPECTOR_LABEL_WIDTH, field, label_color);
+            let subframe = scene.sub_frame((editor_x, ypos), (PROPERTY_ROW_HEIGHT, PROPERTY_ROW_HEIGHT));
+            let action = EditorAction::SetStateColor(state, field, Some(self.color_picker.color()));
+            scene.add_control(Box::new(self.swatch_button(subframe, *color, false, action)));
+            let value = if overridden { hex_string(color) } else { format!("{} (derived)", hex_string(color)) };
+            let origin = (editor_x + PROPERTY_ROW_HEIGHT + 5.0, ypos);
+            add_label(scene, origin, editor_width - PROPERTY_ROW_HEIGHT * 2.0 - 10.0, &value, Color::BLACK);
+            if overridden {
+                let origin = (editor_x + editor_width - PROPERTY_ROW_HEIGHT, ypos);
+                add_button(scene, origin, PROPERTY_ROW_HEIGHT, "×", EditorAction::SetStateColor(state, field, None));
+            }
+            ypos += PROPERTY_ROW_HEIGHT;
+        }
+    }
+
+    /// The "New theme from color…" form, which replaces the other contents of the Properties scene:
+    /// V:|-[title]-|-[seed swatches]-|-[second swatches]-|-[harmonies]-|-[light, dark]-|-[preview]-|-[create]-|
+    fn add_palette_form(&self, scene: &mut Scene, frame: &Rectangle, form: &PaletteForm, mut ypos: f32) {
+        let width = frame.width() - 10.0;
+        let add_label = |scene: &mut Scene, ypos: &mut f32, value: &str| {
+            let subframe = scene.sub_frame((5.0, *ypos), (width, PROPERTY_ROW_HEIGHT));
+            let mut text = Text::new(subframe, value);
+            text.layer.font_style = FontStyle::new(12.0, Color::BLACK);
+            text.text_align(TextAlign::Left);
+            scene.add_control(Box::new(text));
+            *ypos += PROPERTY_ROW_HEIGHT;
+        };
+        // Lays out swatches in rows of SWATCHES_PER_ROW
+        let add_swatches = |scene: &mut Scene, ypos: &mut f32, swatches: Vec<(Color, bool, EditorAction)>| {
+            for (i, (color, selected, action)) in swatches.into_iter().enumerate() {
+                let column = (i % SWATCHES_PER_ROW) as f32;
+                let row = (i / SWATCHES_PER_ROW) as f32;
+                let origin = (5.0 + column * SWATCH_SIZE, *ypos + row * SWATCH_SIZE);
+                let subframe = scene.sub_frame(origin, (SWATCH_SIZE, SWATCH_SIZE));
+                scene.add_control(Box::new(self.swatch_button(subframe, color, selected, action)));
+            }
+            *ypos += SWATCH_SIZE * 2.0 + 5.0;
+        };
+        let swatches = seed_swatches(SWATCHES_PER_ROW * 2 - 1);
+
+        add_label(scene, &mut ypos, "New theme from color");
+        add_label(scene, &mut ypos, &format!("Seed {}", hex_string(&form.seed)));
+        let mut seeds: Vec<(Color, bool, EditorAction)> =
+            swatches.iter().map(|color| (*color, *color == form.seed, EditorAction::PaletteSeed(*color))).collect();
+        let current = self.document.colors.button_bg_color;
+        seeds.insert(0, (current, current == form.seed, EditorAction::PaletteSeed(current)));
+        add_swatches(scene, &mut ypos, seeds);
+
+        let second = form.second.map_or("none".to_string(), |color| hex_string(&color));
+        add_label(scene, &mut ypos, &format!("Second color {}", second));
+        let mut seconds: Vec<(Color, bool, EditorAction)> = swatches
+            .iter()
+            .map(|color| (*color, Some(*color) == form.second, EditorAction::PaletteSecondSeed(Some(*color))))
+            .collect();
+        let none = Color::from_hex("#FFFFFF");
+        seconds.insert(0, (none, form.second.is_none(), EditorAction::PaletteSecondSeed(None)));
+        add_swatches(scene, &mut ypos, seconds);
+
+        let button_width = width / Harmony::ALL.len() as f32;
+        for (i, harmony) in Harmony::ALL.iter().enumerate() {
+            let subframe = scene.sub_frame((5.0 + button_width * i as f32, ypos), (button_width, MINI_BUTTON_SIZE));
+            let mut button = self.action_button(subframe, &harmony.to_string(), EditorAction::PaletteHarmony(*harmony));
+            button.layer.font_style = FontStyle::new(10.0, Color::BLACK);
+            if *harmony == form.options.harmony {
+                button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 2.0);
+            }
+            scene.add_control(Box::new(button));
+        }
+        ypos += MINI_BUTTON_SIZE + 5.0;
+
+        for (i, (title, dark)) in [("Light", false), ("Dark", true)].iter().enumerate() {
+            let subframe = scene.sub_frame((5.0 + width / 2.0 * i as f32, ypos), (width / 2.0, MINI_BUTTON_SIZE));
+            let mut button = self.action_button(subframe, title, EditorAction::PaletteDark(*dark));
+            if *dark == form.options.dark {
+                button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 2.0);
+            }
+            scene.add_control(Box::new(button));
+        }
+        ypos += MINI_BUTTON_SIZE + 5.0;
+
+        // Preview of the generated colors, in ThemeColors field order
+        let document = form.palette().to_document(0, "");
+        add_label(scene, &mut ypos, "Colors");
+        let colors: Vec<(Color, bool, EditorAction)> = ThemeColors::FIELDS
+            .iter()
+            .filter_map(|field| document.colors.get(field))
+            .map(|color| (color, false, EditorAction::PaletteSeed(form.seed)))
+            .collect();
+        add_swatches(scene, &mut ypos, colors);
+
+        let subframe = scene.sub_frame((5.0, ypos), (width / 2.0, MINI_BUTTON_SIZE));
+        let mut button = self.action_button(subframe, "Create", EditorAction::CreateFromPalette);
+        button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 1.0);
+        scene.add_control(Box::new(button));
+        let subframe = scene.sub_frame((5.0 + width / 2.0, ypos), (width / 2.0, MINI_BUTTON_SIZE));
+        let mut button = self.action_button(subframe, "Cancel", EditorAction::NewTheme);
+        button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 1.0);
+        scene.add_control(Box::new(button));
+    }
+
     /// Layout spec:
     ///
-    /// V:|-[Text]-|-
-    ///     H:|-[add button]-|-[remove button]-|
-    /// -|-[empty space]-|
+    /// V:|-[header]-|-[gallery]-|
     ///
+    /// The header has the preview filter buttons above the interaction state buttons:
+    /// H:|-[Normal]-|-[Protanopia]-|-[Deuteranopia]-|-[Tritanopia]-|-[Achromatopsia]-|
+    /// H:|-[Normal]-|-[Hover]-|-[Pressed]-|-[Focused]-|-[Disabled]-|
     fn main_scene(&self, scene_frame: &Rectangle) -> Scene {
         let mut scene = Scene::new(scene_frame.clone()).with_id(2, "Main");
         scene.layer.border_style = BorderStyle::SolidLine(Color::from_hex("#999999"), 1.0);
 
         let layout = self.main_scene_layout(&scene_frame);
 
-        // First button is child path: /Body/Column0/row0
-        let item = &layout.children[1].children[0].children[0];
-        let frame = Rectangle::new((item.location.x, item.location.y), (item.size.width, item.size.height));
-        let mut button = Button::new(frame).with_text("Normal");
-        button.layer.external_id = Some(Box::new(item.id));
-        scene.add_control(Box::new(button));
+        let header = &layout.children[0];
+        let mut filters: Vec<(String, Option<CvdKind>)> = vec![("Normal".to_string(), None)];
+        filters.extend(CvdKind::ALL.iter().map(|kind| (kind.to_string(), Some(*kind))));
+        let button_width = header.size.width / filters.len() as f32;
+        for (i, (title, filter)) in filters.into_iter().enumerate() {
+            let xpos = header.location.x + button_width * i as f32;
+            let frame = Rectangle::new((xpos, header.location.y + 5.0), (button_width, 30.0));
+            let selected = filter == self.preview_filter;
+            let mut button = self.action_button(frame, &title, EditorAction::PreviewFilter(filter));
+            button.layer.font_style = FontStyle::new(12.0, Color::BLACK);
+            if selected {
+                button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 2.0);
+            }
+            scene.add_control(Box::new(button));
+        }
+        let button_width = header.size.width / ControlState::ALL.len() as f32;
+        for (i, state) in ControlState::ALL.iter().enumerate() {
+            let xpos = header.location.x + button_width * i as f32;
+            let frame = Rectangle::new((xpos, header.location.y + 40.0), (button_width, 30.0));
+            let mut button = self.action_button(frame, &state.to_string(), EditorAction::PreviewState(*state));
+            button.layer.font_style = FontStyle::new(12.0, Color::BLACK);
+            if *state == self.preview_state {
+                button.layer.border_style = BorderStyle::SolidLine(Color::BLACK, 2.0);
+            }
+            scene.add_control(Box::new(button));
+        }
 
+        // The gallery fills the body below the header. In compare mode, the body is split into a gallery
+        // for the compared theme on the left and one for the current theme on the right, each locked to
+        // its own theme since the stage applies one theme to all controls.
+        let body = &layout.children[1];
+        match self.compare_document() {
+            Some(other) => {
+                let half = body.size.width / 2.0;
+                let sides = [self.filtered(other.clone()), self.preview_document()];
+                for (i, document) in sides.iter().enumerate() {
+                    let xpos = body.location.x + half * i as f32;
+                    let frame = Rectangle::new((xpos + 5.0, body.location.y), (half - 10.0, PROPERTY_ROW_HEIGHT));
+                    let mut text = Text::new(frame, &document.name);
+                    text.layer.font_style = FontStyle::new(12.0, Color::BLACK);
+                    text.layer.lock_style = true;
+                    text.text_align(TextAlign::Left);
+                    scene.add_control(Box::new(text));
+                    let frame = Rectangle::new(
+                        (xpos, body.location.y + PROPERTY_ROW_HEIGHT),
+                        (half, body.size.height - PROPERTY_ROW_HEIGHT),
+                    );
+                    self.add_gallery(&mut scene, &frame, document, self.preview_state, true);
+                }
+            }
+            None => {
+                let frame = Rectangle::new((body.location.x, body.location.y), (body.size.width, body.size.height));
+                self.add_gallery(&mut scene, &frame, &self.preview_document(), self.preview_state, false);
+            }
+        }
 
         scene
     }
 
-    /// Main Scene layout :
-    ///
-    ///
-    /// Column 1:
-    /// * Text
-    /// * Buttons: normal and primary
-    /// * Text Field
-    /// * Text Area
-    /// Column 2:
-    /// * ListBox
-    /// * Checkbox
-    /// * OptionGroup with radio buttons
+    /// Main Scene layout: a header row with the preview filter and state buttons and a body for the gallery
     /// See: https://vislyhq.github.io/stretch/docs/rust/
     fn main_scene_layout(&self, frame: &Rectangle) -> NodeLayout {
-
-        const HEADER_H: f32 = 50.0;
-        let body_padding = Rect {
-            start: Dimension::Points(10.0),
-            end: Dimension::Points(10.0),
-            top: Dimension::Points(10.0),
-            bottom: Dimension::Points(10.0),
-            ..Default::default()
-        };
-        let item_padding = Rect {
-            start: Dimension::Points(10.0),
-            end: Dimension::Points(10.0),
-            top: Dimension::Points(10.0),
-            bottom: Dimension::Points(10.0),
-            ..Default::default()
-        };
+        const HEADER_H: f32 = 80.0;
         let mut builder = LayoutBuilder::new().with_style(Style {
-                size: Size { width: Dimension::Points(frame.width()), height: Dimension::Points(frame.height()) },
-                flex_direction: FlexDirection::Column,
-                justify_content: JustifyContent::FlexStart,
-                align_items: AlignItems::FlexStart,
-                ..Default::default()
+            size: Size { width: Dimension::Points(frame.width()), height: Dimension::Points(frame.height()) },
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::FlexStart,
+            align_items: AlignItems::FlexStart,
+            ..Default::default()
         });
-        let header_node = builder.add_row(builder.root, HEADER_H, None);
-        let column_w = frame.width()/2.0;
-        let body_node = builder.add_row(builder.root, frame.height() - HEADER_H, None);
-        let column0 = builder.add_column(body_node, frame.width()/2.0, None);
-        let node = builder.add_object(column0, Size { width: column_w, height: 50.0 });
-        let node = builder.add_object(column0, Size { width: column_w, height: 50.0 });
-        let node = builder.add_object(column0, Size { width: column_w, height: 200.0 });
-        let abs_layout = builder.absolute_layout(builder.root, (200.0, 0.0));
-        eprintln!("node_layout={:#?}", abs_layout);
-        abs_layout
+        builder.add_row(builder.root, HEADER_H, None);
+        builder.add_row(builder.root, frame.height() - HEADER_H, None);
+        builder.absolute_layout(builder.root, (frame.x(), frame.y()))
+    }
+
+    /// Add one of every Tweek control to a scene, positioned by gallery_layout and bound to its layout
+    /// node with external_id. The Normal button is drawn with the given theme's background, text and
+    /// border colors, since Tweek buttons are otherwise always drawn in the button colors. In any state
+    /// but Normal, or if locked is set, every control is locked to the theme's style for the state.
+    fn add_gallery(
+        &self,
+        scene: &mut Scene,
+        frame: &Rectangle,
+        document: &ThemeDocument,
+        state: ControlState,
+        locked: bool,
+    ) {
+        let state_style = document.state_style(state);
+        let (layout, nodes) = gallery_layout(frame);
+        for (item, node) in nodes {
+            let node_layout = match layout.find(node) {
+                Some(node_layout) => node_layout,
+                None => continue,
+            };
+            let frame = Rectangle::new(
+                (node_layout.location.x + GALLERY_SPACING, node_layout.location.y + GALLERY_SPACING),
+                (node_layout.size.width - GALLERY_SPACING * 2.0, node_layout.size.height - GALLERY_SPACING * 2.0),
+            );
+            let mut control: Box<dyn Displayable> = match item {
+                GalleryItem::Title => {
+                    let mut text = Text::new(frame, "Title text");
+                    text.layer.font_style = FontStyle::new(document.font_size * 1.5, document.colors.fg_color);
+                    text.text_align(TextAlign::Left);
+                    Box::new(text)
+                }
+                GalleryItem::Body => {
+                    let mut text = Text::new(frame, "Body text in the theme font");
+                    text.text_align(TextAlign::Left);
+                    Box::new(text)
+                }
+                GalleryItem::NormalButton => {
+                    let style = document.state_style(ControlState::Normal);
+                    let mut button = Button::new(frame).with_text("Normal");
+                    button.layer.bg_style = BackgroundStyle::Solid(style.bg_color);
+                    button.layer.font_style = FontStyle::new(document.font_size, style.fg_color);
+                    let width = style.border_width.max(1.0);
+                    button.layer.border_style = BorderStyle::SolidLine(style.border_color, width);
+                    button.layer.lock_style = true;
+                    Box::new(button)
+                }
+                GalleryItem::PrimaryButton => Box::new(Button::new(frame).with_text("Primary")),
+                GalleryItem::EmptyField => Box::new(TextField::new(frame, true)),
+                GalleryItem::FilledField => {
+                    let mut textfield = TextField::new(frame, true);
+                    textfield.set_text("Text field");
+                    Box::new(textfield)
+                }
+                GalleryItem::TextArea => {
+                    let mut textarea = TextArea::new(frame, true);
+                    textarea.set_text("A text area with several lines of text, to judge the theme font at length.");
+                    Box::new(textarea)
+                }
+                GalleryItem::ListBox => {
+                    let mut listbox = ListBox::new(frame);
+                    listbox.row_border_style = BorderStyle::SolidLine(document.colors.border_color, 1.0);
+                    listbox.set_datasource(GALLERY_ROWS.iter().map(|row| row.to_string()).collect());
+                    Box::new(listbox)
+                }
+                GalleryItem::Unchecked => Box::new(Checkbox::new(frame).with_text("Unchecked", false)),
+                GalleryItem::Checked => Box::new(Checkbox::new(frame).with_text("Checked", true)),
+                GalleryItem::Options => {
+                    let mut options = OptionGroup::new(frame);
+                    options.multi_select = false;
+                    options.check_style = CheckStyle::Radio;
+                    options.set_options(GALLERY_OPTIONS.iter().map(|option| option.to_string()).collect(), vec![0]);
+                    Box::new(options)
+                }
+            };
+            let font_size = if item == GalleryItem::Title { document.font_size * 1.5 } else { document.font_size };
+            let layer = control.get_layer_mut();
+            layer.external_id = Some(Box::new(node));
+            if item == GalleryItem::PrimaryButton && state == ControlState::Normal && locked {
+                // Primary buttons rest in the button colors
+                layer.bg_style = BackgroundStyle::Solid(document.colors.button_bg_color);
+                layer.font_style = FontStyle::new(font_size, document.colors.button_fg_color);
+                layer.border_style = BorderStyle::SolidLine(state_style.border_color, state_style.border_width);
+                layer.lock_style = true;
+            } else if state != ControlState::Normal || locked {
+                layer.bg_style = BackgroundStyle::Solid(state_style.bg_color);
+                layer.font_style = FontStyle::new(font_size, state_style.fg_color);
+                layer.border_style = BorderStyle::SolidLine(state_style.border_color, state_style.border_width);
+                layer.lock_style = true;
+            }
+            scene.add_control(control);
+        }
     }
 }
 
@@ -264,13 +1720,147 @@ impl Controller for ThemeEditor {
         //     ctx.event_bus.register_event(evt);
         // }
 
+        self.frames += 1;
+        let actions: Vec<EditorAction> = self.actions.borrow_mut().drain(..).collect();
+        for action in actions {
+            match action {
+                EditorAction::OpenThemes => self.open_themes(),
+                EditorAction::SaveTheme => self.save_theme(),
+                EditorAction::ImportVscode => self.import_vscode_themes(),
+                EditorAction::ExportVscode => self.export_vscode_theme(),
+                EditorAction::ExportCss => self.export_css(),
+                EditorAction::ExportRust => self.export_rust(),
+                EditorAction::PreviewFilter(filter) => {
+                    self.preview_filter = filter;
+                    self.reload_stage();
+                }
+                EditorAction::NewTheme => {
+                    self.create_menu = false;
+                    self.toggle_palette_form();
+                }
+                EditorAction::PaletteSeed(color) => self.update_palette_form(|form| form.seed = color),
+                EditorAction::PaletteSecondSeed(color) => self.update_palette_form(|form| form.second = color),
+                EditorAction::PaletteHarmony(harmony) => {
+                    self.update_palette_form(|form| form.options.harmony = harmony)
+                }
+                EditorAction::PaletteDark(dark) => self.update_palette_form(|form| form.options.dark = dark),
+                EditorAction::CreateFromPalette => self.create_from_palette(),
+                EditorAction::Picker(action) => self.picker_action(action),
+                EditorAction::StepField(path, amount) => self.step_field(path, amount),
+                EditorAction::ToggleDropdown(path) => {
+                    self.open_dropdown = if self.open_dropdown == Some(path) { None } else { Some(path) };
+                    self.reload_stage();
+                }
+                EditorAction::SetField(path, value) => {
+                    self.open_dropdown = None;
+                    if !self.set_field_value(path, value) {
+                        self.reload_stage();
+                    }
+                }
+                EditorAction::SelectTheme(id) => self.select_theme(id),
+                EditorAction::BeginRename => {
+                    self.name_entry = Some(self.document.name.clone());
+                    self.reload_stage();
+                }
+                EditorAction::CommitRename => self.commit_rename(),
+                EditorAction::CancelRename => {
+                    self.name_entry = None;
+                    self.reload_stage();
+                }
+                EditorAction::ToggleCreateMenu => {
+                    self.create_menu = !self.create_menu;
+                    self.reload_stage();
+                }
+                EditorAction::CreateTheme(kind) => self.create_theme(kind),
+                EditorAction::DeleteTheme => self.delete_theme(),
+                EditorAction::ConfirmDelete(id) => self.confirm_delete(id),
+                EditorAction::Undo => self.undo_command(),
+                EditorAction::Redo => self.redo_command(),
+                EditorAction::Quit { save } => self.quit(save, window),
+                EditorAction::Recover(restore) => self.recover(restore),
+                EditorAction::PreviewState(state) => {
+                    self.preview_state = state;
+                    self.reload_stage();
+                }
+                EditorAction::SetStateColor(state, field, color) => self.set_state_color(state, field, color),
+                EditorAction::Compare(id) => {
+                    self.compare_id = id;
+                    self.reload_stage();
+                }
+            }
+        }
+
         let _ = self.stage.update(window, state);
 
     }
 
+    /// The focus ring is drawn over the stage with the focused state colors of the current theme
     fn render(&mut self, theme: &mut Theme, window: &mut Window) {
         let _ = self.stage.render(theme, window);
         // let _ = self.navbar.render(theme, window);
+        if let Some(target) = self.focus.borrow().focused() {
+            let style = self.document.state_style(ControlState::Focused);
+            draw_focus_ring(window, &target.frame, style.border_color, style.border_width);
+        }
+    }
+
+    fn next_modal(&mut self) -> Option<Box<dyn Controller>> {
+        self.modal.take()
+    }
+
+    /// With unsaved changes, ask whether to save them before quitting
+    fn close_requested(&mut self) -> bool {
+        let names: Vec<String> = self.unsaved_themes().into_iter().map(|theme| theme.name).collect();
+        if names.is_empty() {
+            self.remove_journal();
+            return true;
+        }
+        let message = format!("Unsaved changes in: {}. Discarded changes cannot be recovered.", names.join(", "));
+        let title = "Save changes before quitting?";
+        let dialog = ConfirmController::new(self.frame.clone(), title, &message, self.actions.clone())
+            .with_choice("Cancel", None)
+            .with_choice("Discard", Some(EditorAction::Quit { save: false }))
+            .with_choice("Save", Some(EditorAction::Quit { save: true }));
+        self.modal = Some(Box::new(dialog));
+        false
+    }
+
+    /// Journal the unsaved themes, unless a journal found at launch has not been answered yet
+    fn autosave(&mut self) {
+        if self.recovery.is_none() {
+            self.write_journal();
+        }
+    }
+
+    /// Offer to restore the themes of a journal left by a session that did not shut down cleanly
+    fn check_recovery(&mut self) {
+        let path = self.journal_path();
+        let journal = match RecoveryJournal::load(&path) {
+            Ok(Some(journal)) if !journal.themes.is_empty() => journal,
+            Ok(_) => return,
+            Err(err) => {
+                log::error!("Could not read {:?}: {}", path, err);
+                return;
+            }
+        };
+        let message = format!(
+            "The last session did not quit normally. Unsaved changes to {} can be restored.",
+            journal.theme_names().join(", ")
+        );
+        let title = "Restore unsaved themes?";
+        let dialog = ConfirmController::new(self.frame.clone(), title, &message, self.actions.clone())
+            .with_choice("Discard", Some(EditorAction::Recover(false)))
+            .with_choice("Restore", Some(EditorAction::Recover(true)));
+        self.recovery = Some(journal);
+        self.modal = Some(Box::new(dialog));
+    }
+
+    fn undo(&mut self) {
+        self.actions.borrow_mut().push(EditorAction::Undo);
+    }
+
+    fn redo(&mut self) {
+        self.actions.borrow_mut().push(EditorAction::Redo);
     }
 
     fn handle_mouse_at(&mut self, pt: &Vector, window: &mut Window) -> bool {
@@ -279,7 +1869,7 @@ impl Controller for ThemeEditor {
     }
 
     fn handle_mouse_down(&mut self, pt: &Vector, state: &mut AppState) -> bool {
-        println!(">>> handle_mouse_down");
+        self.focus.get_mut().clear_focus();
         // if let Some(ref mut rc) = self.nav.upgrade() {
         //     let mut nav = rc.borrow_mut();
         //     (&mut *nav).notify("Booo");
@@ -295,5 +1885,139 @@ impl Controller for ThemeEditor {
     fn handle_mouse_scroll(&mut self, pt: &Vector, state: &mut AppState) {
         self.stage.handle_mouse_scroll(pt, state);
     }
+
+    /// Typed text goes to the theme name or the color picker's text entry while one is being edited, and
+    /// otherwise to the focused control of the stage
+    fn handle_key_press(&mut self, c: char, window: &mut Window) {
+        if self.name_entry.is_some() || self.color_picker.entry().is_some() {
+            if !c.is_control() {
+                self.type_text(&c.to_string());
+            }
+            return;
+        }
+        // Tab moves the focus, and Space activates the focused button
+        if c == '\t' || (c == ' ' && self.focus.get_mut().focused().is_some()) {
+            return;
+        }
+        self.stage.handle_key_press(c, window);
+    }
+
+    /// Editing keys for the theme name and the color picker's text entry, Tab and Shift+Tab to move the
+    /// focus, Enter or Space to activate the focused button, Escape to close open menus and Cmd/Ctrl+S to
+    /// save. Other keys go to the focused control of the stage.
+    fn handle_key_command(&mut self, key: &Key, window: &mut Window) -> bool {
+        if self.name_entry.is_some() {
+            match key {
+                Key::Back => self.backspace(),
+                Key::Return | Key::NumpadEnter => self.commit_rename(),
+                Key::Escape => {
+                    self.name_entry = None;
+                    self.reload_stage();
+                }
+                _ => return false,
+            }
+            return true;
+        }
+        if self.color_picker.entry().is_some() {
+            let action = match key {
+                Key::Back => {
+                    self.backspace();
+                    return true;
+                }
+                Key::Return | Key::NumpadEnter => PickerAction::CommitEntry,
+                Key::Escape => PickerAction::CancelEntry,
+                _ => return false,
+            };
+            self.picker_action(action);
+            return true;
+        }
+        match key {
+            Key::Tab => {
+                self.focus.get_mut().advance(!is_shift_down(window));
+                true
+            }
+            Key::Return | Key::NumpadEnter | Key::Space => {
+                let action = match self.focus.get_mut().focused() {
+                    Some(target) => target.action.clone(),
+                    None => return self.stage.handle_key_command(key, window),
+                };
+                self.actions.borrow_mut().push(action);
+                true
+            }
+            Key::Escape if self.open_dropdown.is_some() || self.create_menu => {
+                self.open_dropdown = None;
+                self.create_menu = false;
+                self.reload_stage();
+                true
+            }
+            Key::S if is_command_down(window) => {
+                self.save_theme();
+                true
+            }
+            _ => self.stage.handle_key_command(key, window),
+        }
+    }
 }
 
+/// The names of the font files in a directory, sorted. Falls back to the default theme fonts if the
+/// directory cannot be read or has no fonts.
+fn font_files(dir: &Path) -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| name.ends_with(".ttf") || name.ends_with(".otf"))
+                .collect()
+        })
+        .unwrap_or_default();
+    if files.is_empty() {
+        let fonts = ThemeFonts::default();
+        files = vec![fonts.default, fonts.title];
+    }
+    files.sort();
+    files
+}
+
+/// Gallery layout, two columns of controls:
+///
+/// Column 1:
+/// * Title and body text
+/// * Buttons: normal and primary
+/// * Text fields: empty and filled
+/// * Text Area
+/// Column 2:
+/// * ListBox
+/// * Checkboxes: unchecked and checked
+/// * OptionGroup with radio buttons
+///
+/// Returns the layout and the node of each control.
+fn gallery_layout(frame: &Rectangle) -> (NodeLayout, Vec<(GalleryItem, Node)>) {
+    let column_w = frame.width() / 2.0;
+    let mut builder = LayoutBuilder::new().with_style(Style {
+        size: Size { width: Dimension::Points(frame.width()), height: Dimension::Points(frame.height()) },
+        flex_direction: FlexDirection::Row,
+        justify_content: JustifyContent::FlexStart,
+        align_items: AlignItems::FlexStart,
+        ..Default::default()
+    });
+    let mut nodes: Vec<(GalleryItem, Node)> = Vec::new();
+    let column0 = builder.add_column(builder.root, column_w, None);
+    nodes.push((GalleryItem::Title, builder.add_object(column0, Size { width: column_w, height: 50.0 })));
+    nodes.push((GalleryItem::Body, builder.add_object(column0, Size { width: column_w, height: 30.0 })));
+    let buttons = builder.add_row(column0, 50.0, None);
+    let button_size = Size { width: column_w / 2.0, height: 50.0 };
+    nodes.push((GalleryItem::NormalButton, builder.add_object(buttons, button_size)));
+    nodes.push((GalleryItem::PrimaryButton, builder.add_object(buttons, button_size)));
+    nodes.push((GalleryItem::EmptyField, builder.add_object(column0, Size { width: column_w, height: 50.0 })));
+    nodes.push((GalleryItem::FilledField, builder.add_object(column0, Size { width: column_w, height: 50.0 })));
+    nodes.push((GalleryItem::TextArea, builder.add_object(column0, Size { width: column_w, height: 150.0 })));
+
+    let column1 = builder.add_column(builder.root, column_w, None);
+    nodes.push((GalleryItem::ListBox, builder.add_object(column1, Size { width: column_w, height: 160.0 })));
+    nodes.push((GalleryItem::Unchecked, builder.add_object(column1, Size { width: column_w, height: 40.0 })));
+    nodes.push((GalleryItem::Checked, builder.add_object(column1, Size { width: column_w, height: 40.0 })));
+    nodes.push((GalleryItem::Options, builder.add_object(column1, Size { width: column_w, height: 120.0 })));
+
+    (builder.absolute_layout(builder.root, (frame.x(), frame.y())), nodes)
+}
@@ -0,0 +1,130 @@
+/// Keyboard focus for the buttons of the editor. Every button made with ThemeEditor::action_button is
+/// added to a FocusChain while the stage is built. Tab and Shift+Tab move the focus through the columns
+/// from left to right, and within a column from top to bottom and left to right. Enter or Space queues
+/// the action of the focused button, as a click would.
+///
+/// The stage is rebuilt after most changes, so the focus is kept by the title and action of the button
+/// rather than by the control itself.
+///
+use quicksilver::{
+    geom::{Line, Rectangle, Vector},
+    graphics::{Background::Col, Color},
+    lifecycle::Window,
+};
+
+use super::EditorAction;
+
+/// A button that can take keyboard focus
+#[derive(Debug, Clone, PartialEq)]
+pub struct FocusTarget {
+    /// The button frame in window coordinates
+    pub frame: Rectangle,
+    pub title: String,
+    pub action: EditorAction,
+    /// The index of the column the button is in, from left to right
+    pub column: usize,
+}
+
+impl FocusTarget {
+    /// True for the same button in a rebuilt stage
+    fn same_button(&self, other: &FocusTarget) -> bool {
+        self.title == other.title && self.action == other.action
+    }
+
+    /// The position in tab order: column, then top to bottom, then left to right
+    fn tab_position(&self) -> (usize, f32, f32) {
+        (self.column, self.frame.y(), self.frame.x())
+    }
+}
+
+/// The focusable buttons of a stage in tab order, and the focused one
+#[derive(Debug, Clone, Default)]
+pub struct FocusChain {
+    /// The x position of the left edge of each column, in order
+    columns: Vec<f32>,
+    targets: Vec<FocusTarget>,
+    focused: Option<FocusTarget>,
+}
+
+impl FocusChain {
+    pub fn new() -> Self {
+        FocusChain::default()
+    }
+
+    /// Remove the targets before the stage is rebuilt. The focus is kept, and is found again when the
+    /// same button is added.
+    pub fn reset(&mut self, columns: Vec<f32>) {
+        self.columns = columns;
+        self.targets.clear();
+    }
+
+    /// Add a focusable button
+    pub fn add(&mut self, frame: Rectangle, title: &str, action: EditorAction) {
+        let column = self.columns.iter().filter(|left| frame.x() >= **left).count().saturating_sub(1);
+        let target = FocusTarget { frame, title: title.to_string(), action, column };
+        // Keep the targets sorted in tab order. Targets at the same position keep the order they were added.
+        let index = self
+            .targets
+            .iter()
+            .position(|other| other.tab_position() > target.tab_position())
+            .unwrap_or(self.targets.len());
+        self.targets.insert(index, target);
+    }
+
+    /// The focused button, if it is still in the stage
+    pub fn focused(&self) -> Option<&FocusTarget> {
+        let focused = self.focused.as_ref()?;
+        self.targets.iter().find(|target| target.same_button(focused))
+    }
+
+    /// Move the focus to the next button in tab order, or the previous one if forward is false. Without
+    /// focus, Tab starts at the first button and Shift+Tab at the last one. The focus wraps around.
+    pub fn advance(&mut self, forward: bool) {
+        if self.targets.is_empty() {
+            self.focused = None;
+            return;
+        }
+        let count = self.targets.len();
+        let index = match &self.focused {
+            Some(focused) => match self.targets.iter().position(|target| target.same_button(focused)) {
+                Some(index) if forward => (index + 1) % count,
+                Some(index) => (index + count - 1) % count,
+                // The focused button is gone, so continue from where it was
+                None => {
+                    let next = self.targets.iter().position(|target| target.tab_position() > focused.tab_position());
+                    match next {
+                        Some(index) if forward => index,
+                        Some(index) => (index + count - 1) % count,
+                        None if forward => 0,
+                        None => count - 1,
+                    }
+                }
+            },
+            None if forward => 0,
+            None => count - 1,
+        };
+        self.focused = Some(self.targets[index].clone());
+    }
+
+    /// Remove the focus, eg: when the mouse is used
+    pub fn clear_focus(&mut self) {
+        self.focused = None;
+    }
+}
+
+/// Draw a ring around a focused control, just outside its frame
+pub fn draw_focus_ring(window: &mut Window, frame: &Rectangle, color: Color, width: f32) {
+    let inset = width / 2.0;
+    let (left, top) = (frame.x() - inset, frame.y() - inset);
+    let (right, bottom) = (frame.x() + frame.width() + inset, frame.y() + frame.height() + inset);
+    let corners = [
+        Vector::new(left, top),
+        Vector::new(right, top),
+        Vector::new(right, bottom),
+        Vector::new(left, bottom),
+    ];
+    for (i, start) in corners.iter().enumerate() {
+        let end = corners[(i + 1) % corners.len()];
+        window.draw(&Line::new(*start, end).with_thickness(width), Col(color));
+    }
+}
@@ -1,5 +1,11 @@
+pub use self::color_picker::*;
+pub use self::confirm::*;
 pub use self::editor::*;
+pub use self::focus::*;
 // pub use self::home::*;
 
+mod color_picker;
+mod confirm;
 mod editor;
+mod focus;
 // mod home;
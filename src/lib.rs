@@ -1,4 +1,6 @@
 pub mod application;
+pub mod cli;
 pub mod controllers;
 // pub mod prelude;
+pub mod theme;
 pub mod utils;
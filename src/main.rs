@@ -1,4 +1,5 @@
 use tweek_theme::application::*;
+use tweek_theme::cli;
 
 use quicksilver::{
     geom::Vector,
@@ -6,6 +7,11 @@ use quicksilver::{
 };
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run_command(&args) {
+        std::process::exit(code);
+    }
+
     let screen = Vector::new(1000, 600);
     run_with("Theme Edit", screen, Settings::default(), || Application::new(screen));
 }
@@ -1 +1 @@
-pub use crate::{application::*, controllers::*, utils::*};
+pub use crate::{application::*, controllers::*, theme::*, utils::*};
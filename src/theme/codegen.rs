@@ -0,0 +1,123 @@
+/// Rust code generation for themes. The generated module has the theme values as constants plus a
+/// ThemeBuilder-style constructor, so a theme made in the editor can be added to a crate as a file.
+///
+/// //! Theme "My theme". Generated by the Tweek theme editor.
+/// use quicksilver::graphics::Color;
+/// use tweek::gui::Theme;
+///
+/// pub const FONT_SIZE: f32 = 14.0;
+/// pub const BG_COLOR: Color = Color {
+///     r: 0.9843137,
+///     ...
+/// };
+/// pub fn my_theme() -> Theme {
+///     let mut theme = Theme::default();
+///     theme.bg_color = BG_COLOR;
+///     ...
+///     theme
+/// }
+///
+use super::*;
+
+/// Strict and reserved Rust keywords, which cannot be function names. The slug is lowercase, so Self
+/// becomes self.
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+    "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro", "match", "mod",
+    "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true",
+    "try", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// The name of the generated constructor for a theme. Eg: "My theme" becomes "my_theme". Names that
+/// are Rust keywords get a _theme suffix rather than r#, since the name is also used as the file name.
+/// Eg: "Type" becomes "type_theme"
+pub fn rust_function_name(document: &ThemeDocument) -> String {
+    let name = document.slug().replace('-', "_");
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("theme_{}", name)
+    } else if RUST_KEYWORDS.contains(&name.as_str()) {
+        format!("{}_theme", name)
+    } else {
+        name
+    }
+}
+
+/// Generate a Rust module for a theme with a constructor function named fn_name
+pub fn generate_rust_module(document: &ThemeDocument, fn_name: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    lines.push(format!("//! Theme {:?}. Generated by the Tweek theme editor.", document.name));
+    lines.push(format!("//! Fonts: {:?} and {:?}", document.fonts.default, document.fonts.title));
+    lines.push(String::new());
+    lines.push("use quicksilver::graphics::Color;".to_string());
+    lines.push("use tweek::gui::Theme;".to_string());
+    lines.push(String::new());
+
+    lines.push(format!("pub const FONT_SIZE: f32 = {:?};", document.font_size));
+    let border_width = match document.border.style {
+        BorderKind::None => 0.0,
+        BorderKind::Solid => document.border.width,
+    };
+    lines.push(format!("pub const BORDER_WIDTH: f32 = {:?};", border_width));
+    for field in ThemeColors::FIELDS.iter() {
+        // Laid out the way rustfmt formats struct literals with default settings
+        if let Some(color) = document.colors.get(field) {
+            lines.push(format!("pub const {}: Color = Color {{", field.to_uppercase()));
+            lines.push(format!("    r: {:?},", color.r));
+            lines.push(format!("    g: {:?},", color.g));
+            lines.push(format!("    b: {:?},", color.b));
+            lines.push(format!("    a: {:?},", color.a));
+            lines.push("};".to_string());
+        }
+    }
+    lines.push(String::new());
+
+    lines.push(format!("/// {}", document.name));
+    lines.push(format!("pub fn {}() -> Theme {{", fn_name));
+    lines.push("    let mut theme = Theme::default();".to_string());
+    lines.push("    theme.font_size = FONT_SIZE;".to_string());
+    lines.push("    theme.border_width = BORDER_WIDTH;".to_string());
+    for field in ThemeColors::FIELDS.iter() {
+        lines.push(format!("    theme.{} = {};", field, field.to_uppercase()));
+    }
+    lines.push("    theme".to_string());
+    lines.push("}".to_string());
+
+    let mut source = lines.join("\n");
+    source.push('\n');
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function_name(name: &str) -> String {
+        let mut document = builtin_themes().remove(0);
+        document.name = name.to_string();
+        rust_function_name(&document)
+    }
+
+    #[test]
+    fn function_names() {
+        assert_eq!(function_name("My theme"), "my_theme");
+        assert_eq!(function_name("Night Owl (dark)"), "night_owl_dark");
+        assert_eq!(function_name("2020 theme"), "theme_2020_theme");
+    }
+
+    #[test]
+    fn keywords_get_a_suffix() {
+        for name in ["Type", "Static", "Loop", "Match", "Fn", "Self", "async"].iter() {
+            assert_eq!(function_name(name), format!("{}_theme", name.to_lowercase()));
+        }
+        assert_eq!(function_name("Typed"), "typed");
+    }
+
+    #[test]
+    fn module_uses_function_name() {
+        let mut document = builtin_themes().remove(0);
+        document.name = "Match".to_string();
+        let fn_name = rust_function_name(&document);
+        let module = generate_rust_module(&document, &fn_name);
+        assert!(module.contains("pub fn match_theme() -> Theme {"));
+    }
+}
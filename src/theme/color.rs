@@ -0,0 +1,472 @@
+/// Color spaces and perceptual color operations for quicksilver Colors. Each space has a struct
+/// with from_color and to_color, so a color can be edited in whichever space suits the change:
+///
+/// let mut lch = Oklch::from_color(&Color::from_hex("#2AA298"));
+/// lch.l += 0.1;
+/// let lighter = lch.to_color();
+///
+/// * Hsl and Hsv: cylindrical sRGB, as used by most color pickers
+/// * Lab: CIELAB with the D65 white point, lightness from 0 to 100
+/// * Oklab and Oklch: the perceptual space by Björn Ottosson, lightness from 0 to 1
+///
+/// Hues are in degrees from 0 to 360 and alpha is carried through every conversion. Converting back
+/// to sRGB never returns channels outside 0 to 1: Oklch reduces the chroma to stay in gamut and the
+/// other spaces clamp the channels.
+///
+use quicksilver::graphics::Color;
+
+/// Sample steps for the chroma search in Oklch::to_color
+const GAMUT_SEARCH_STEPS: usize = 16;
+/// The D65 white point in XYZ, used by CIELAB
+const D65_WHITE: [f32; 3] = [0.95047, 1.0, 1.08883];
+
+/// A color in HSL. Saturation and lightness are 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsl {
+    pub h: f32,
+    pub s: f32,
+    pub l: f32,
+    pub a: f32,
+}
+
+/// A color in HSV. Saturation and value are 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsv {
+    pub h: f32,
+    pub s: f32,
+    pub v: f32,
+    pub a: f32,
+}
+
+/// A color in CIELAB (D65). Lightness is 0 to 100, and a and b are roughly -128 to 127.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+    pub alpha: f32,
+}
+
+/// A color in Oklab. Lightness is 0 to 1, and a and b are roughly -0.4 to 0.4.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Oklab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+    pub alpha: f32,
+}
+
+/// A color in OKLCH, the cylindrical form of Oklab. Lightness is 0 to 1 and chroma is 0 to about 0.37.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Oklch {
+    pub l: f32,
+    pub c: f32,
+    pub h: f32,
+    pub alpha: f32,
+}
+
+impl Hsl {
+    pub fn from_color(color: &Color) -> Self {
+        let max = color.r.max(color.g).max(color.b);
+        let min = color.r.min(color.g).min(color.b);
+        let l = (max + min) / 2.0;
+        if max == min {
+            return Hsl { h: 0.0, s: 0.0, l, a: color.a };
+        }
+        let d = max - min;
+        let s = if l > 0.5 { d / (2.0 - max - min) } else { d / (max + min) };
+        Hsl { h: rgb_hue(color, max, d), s, l, a: color.a }
+    }
+
+    pub fn to_color(&self) -> Color {
+        let (s, l) = (clamp_unit(self.s), clamp_unit(self.l));
+        let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        let (r, g, b) = hue_to_rgb(self.h, c);
+        let m = l - c / 2.0;
+        clamp_to_gamut(&Color { r: r + m, g: g + m, b: b + m, a: self.a })
+    }
+}
+
+impl Hsv {
+    pub fn from_color(color: &Color) -> Self {
+        let max = color.r.max(color.g).max(color.b);
+        let min = color.r.min(color.g).min(color.b);
+        let d = max - min;
+        let s = if max == 0.0 { 0.0 } else { d / max };
+        let h = if d == 0.0 { 0.0 } else { rgb_hue(color, max, d) };
+        Hsv { h, s, v: max, a: color.a }
+    }
+
+    pub fn to_color(&self) -> Color {
+        let (s, v) = (clamp_unit(self.s), clamp_unit(self.v));
+        let c = v * s;
+        let (r, g, b) = hue_to_rgb(self.h, c);
+        let m = v - c;
+        clamp_to_gamut(&Color { r: r + m, g: g + m, b: b + m, a: self.a })
+    }
+}
+
+impl Lab {
+    pub fn from_color(color: &Color) -> Self {
+        let (r, g, b) = (srgb_to_linear(color.r), srgb_to_linear(color.g), srgb_to_linear(color.b));
+        let x = 0.4124564 * r + 0.3575761 * g + 0.1804375 * b;
+        let y = 0.2126729 * r + 0.7151522 * g + 0.072175 * b;
+        let z = 0.0193339 * r + 0.119192 * g + 0.9503041 * b;
+        let f = |t: f32| if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 };
+        let (fx, fy, fz) = (f(x / D65_WHITE[0]), f(y / D65_WHITE[1]), f(z / D65_WHITE[2]));
+        Lab { l: 116.0 * fy - 16.0, a: 500.0 * (fx - fy), b: 200.0 * (fy - fz), alpha: color.a }
+    }
+
+    pub fn to_color(&self) -> Color {
+        let fy = (self.l + 16.0) / 116.0;
+        let fx = fy + self.a / 500.0;
+        let fz = fy - self.b / 200.0;
+        let f_inv = |t: f32| if t.powi(3) > 216.0 / 24389.0 { t.powi(3) } else { (116.0 * t - 16.0) * 27.0 / 24389.0 };
+        let (x, y, z) = (f_inv(fx) * D65_WHITE[0], f_inv(fy) * D65_WHITE[1], f_inv(fz) * D65_WHITE[2]);
+        let r = 3.2404542 * x - 1.5371385 * y - 0.4985314 * z;
+        let g = -0.969266 * x + 1.8760108 * y + 0.041556 * z;
+        let b = 0.0556434 * x - 0.2040259 * y + 1.0572252 * z;
+        linear_to_color([r, g, b], self.alpha)
+    }
+
+    /// The CIE76 color difference. About 2.3 is the smallest difference most people notice.
+    pub fn distance(&self, other: &Lab) -> f32 {
+        ((self.l - other.l).powi(2) + (self.a - other.a).powi(2) + (self.b - other.b).powi(2)).sqrt()
+    }
+}
+
+impl Oklab {
+    pub fn from_color(color: &Color) -> Self {
+        let (r, g, b) = (srgb_to_linear(color.r), srgb_to_linear(color.g), srgb_to_linear(color.b));
+        let l = (0.41222146 * r + 0.53633255 * g + 0.051445995 * b).cbrt();
+        let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+        let s = (0.08830246 * r + 0.28171885 * g + 0.6299787 * b).cbrt();
+        Oklab {
+            l: 0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+            a: 1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+            b: 0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+            alpha: color.a,
+        }
+    }
+
+    /// Convert to sRGB, clamping the channels. Use Oklch::to_color to keep the hue instead.
+    pub fn to_color(&self) -> Color {
+        linear_to_color(self.to_linear(), self.alpha)
+    }
+
+    /// Linear sRGB channels, which are outside 0 to 1 if the color is out of gamut
+    fn to_linear(&self) -> [f32; 3] {
+        let l = (self.l + 0.39633778 * self.a + 0.21580376 * self.b).powi(3);
+        let m = (self.l - 0.105561346 * self.a - 0.06385417 * self.b).powi(3);
+        let s = (self.l - 0.08948418 * self.a - 1.2914855 * self.b).powi(3);
+        [
+            4.0767417 * l - 3.3077116 * m + 0.23096994 * s,
+            -1.268438 * l + 2.6097574 * m - 0.34131938 * s,
+            -0.0041960864 * l - 0.7034186 * m + 1.7076147 * s,
+        ]
+    }
+}
+
+impl Oklch {
+    pub fn from_color(color: &Color) -> Self {
+        let lab = Oklab::from_color(color);
+        let hue = lab.b.atan2(lab.a).to_degrees();
+        Oklch {
+            l: lab.l,
+            c: (lab.a * lab.a + lab.b * lab.b).sqrt(),
+            h: if hue < 0.0 { hue + 360.0 } else { hue },
+            alpha: lab.alpha,
+        }
+    }
+
+    pub fn to_oklab(&self) -> Oklab {
+        let h = self.h.to_radians();
+        Oklab { l: self.l, a: self.c * h.cos(), b: self.c * h.sin(), alpha: self.alpha }
+    }
+
+    /// Convert to sRGB. Colors outside the sRGB gamut keep their lightness and hue and lose chroma.
+    pub fn to_color(&self) -> Color {
+        let l = clamp_unit(self.l);
+        let in_gamut = |c: f32| {
+            let rgb = Oklch { l, c, ..*self }.to_oklab().to_linear();
+            rgb.iter().all(|c| *c >= -0.0001 && *c <= 1.0001)
+        };
+        let mut chroma = self.c.max(0.0);
+        if !in_gamut(chroma) {
+            let (mut low, mut high) = (0.0, chroma);
+            for _ in 0..GAMUT_SEARCH_STEPS {
+                let mid = (low + high) / 2.0;
+                if in_gamut(mid) {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            chroma = low;
+        }
+        Oklch { l, c: chroma, ..*self }.to_oklab().to_color()
+    }
+}
+
+/// Decode an sRGB channel to linear light
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a linear light channel as sRGB
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// True if every channel is between 0 and 1
+pub fn is_in_gamut(color: &Color) -> bool {
+    [color.r, color.g, color.b, color.a].iter().all(|c| *c >= 0.0 && *c <= 1.0)
+}
+
+/// Clamp every channel to 0 to 1
+pub fn clamp_to_gamut(color: &Color) -> Color {
+    Color { r: clamp_unit(color.r), g: clamp_unit(color.g), b: clamp_unit(color.b), a: clamp_unit(color.a) }
+}
+
+/// Change the OKLCH lightness by amount, which is negative to darken. Eg: 0.1 for 10% lighter.
+pub fn lighten(color: &Color, amount: f32) -> Color {
+    let mut lch = Oklch::from_color(color);
+    lch.l = clamp_unit(lch.l + amount);
+    lch.to_color()
+}
+
+/// Change the OKLCH chroma by a fraction of its current value, which is negative to desaturate.
+/// Eg: 0.2 for 20% more colorful, -1.0 for gray.
+pub fn saturate(color: &Color, amount: f32) -> Color {
+    let mut lch = Oklch::from_color(color);
+    lch.c = (lch.c * (1.0 + amount)).max(0.0);
+    lch.to_color()
+}
+
+/// Blend from a to b by weight (0 to 1) in Oklab, which avoids the dark and gray midpoints of
+/// blending in sRGB
+pub fn mix(a: &Color, b: &Color, weight: f32) -> Color {
+    let w = clamp_unit(weight);
+    let (a, b) = (Oklab::from_color(a), Oklab::from_color(b));
+    let lab = Oklab {
+        l: a.l + (b.l - a.l) * w,
+        a: a.a + (b.a - a.a) * w,
+        b: a.b + (b.b - a.b) * w,
+        alpha: a.alpha + (b.alpha - a.alpha) * w,
+    };
+    lab.to_color()
+}
+
+/// Blend from a to b by weight (0 to 1) per sRGB channel, like CSS and Sass mix functions
+pub fn mix_srgb(a: &Color, b: &Color, weight: f32) -> Color {
+    let w = clamp_unit(weight);
+    Color {
+        r: a.r + (b.r - a.r) * w,
+        g: a.g + (b.g - a.g) * w,
+        b: a.b + (b.b - a.b) * w,
+        a: a.a + (b.a - a.a) * w,
+    }
+}
+
+/// The perceptual distance between two colors, as the Euclidean distance in Oklab. Colors that
+/// differ by less than about 0.02 look the same.
+pub fn color_distance(a: &Color, b: &Color) -> f32 {
+    let (a, b) = (Oklab::from_color(a), Oklab::from_color(b));
+    ((a.l - b.l).powi(2) + (a.a - b.a).powi(2) + (a.b - b.b).powi(2)).sqrt()
+}
+
+fn clamp_unit(value: f32) -> f32 {
+    value.max(0.0).min(1.0)
+}
+
+fn linear_to_color(rgb: [f32; 3], alpha: f32) -> Color {
+    let encode = |c: f32| linear_to_srgb(clamp_unit(c));
+    Color { r: encode(rgb[0]), g: encode(rgb[1]), b: encode(rgb[2]), a: alpha }
+}
+
+/// The hue in degrees of an sRGB color with the given max channel and max - min
+fn rgb_hue(color: &Color, max: f32, d: f32) -> f32 {
+    let h = if max == color.r {
+        (color.g - color.b) / d + if color.g < color.b { 6.0 } else { 0.0 }
+    } else if max == color.g {
+        (color.b - color.r) / d + 2.0
+    } else {
+        (color.r - color.g) / d + 4.0
+    };
+    h * 60.0
+}
+
+/// The rgb channels for a hue with chroma c, before adding the lightness offset
+fn hue_to_rgb(hue: f32, c: f32) -> (f32, f32, f32) {
+    let h = (hue % 360.0 + 360.0) % 360.0 / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: [(f32, f32, f32, f32); 7] = [
+        (0.0, 0.0, 0.0, 1.0),
+        (1.0, 1.0, 1.0, 1.0),
+        (1.0, 0.0, 0.0, 1.0),
+        (0.0, 0.5, 1.0, 0.5),
+        (0.165, 0.635, 0.596, 1.0),
+        (0.9, 0.8, 0.1, 0.25),
+        (0.3, 0.3, 0.3, 1.0),
+    ];
+
+    fn samples() -> Vec<Color> {
+        SAMPLES.iter().map(|(r, g, b, a)| Color { r: *r, g: *g, b: *b, a: *a }).collect()
+    }
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!((actual - expected).abs() <= tolerance, "{} != {}", actual, expected);
+    }
+
+    fn assert_color(actual: Color, expected: Color) {
+        let channels = |c: Color| [c.r, c.g, c.b, c.a];
+        let close = channels(actual).iter().zip(channels(expected).iter()).all(|(a, e)| (a - e).abs() < 0.002);
+        assert!(close, "{:?} != {:?}", actual, expected);
+    }
+
+    fn is_gray(color: Color) -> bool {
+        (color.r - color.g).abs() < 0.002 && (color.g - color.b).abs() < 0.002
+    }
+
+    #[test]
+    fn round_trips() {
+        for color in samples() {
+            assert_color(Hsl::from_color(&color).to_color(), color);
+            assert_color(Hsv::from_color(&color).to_color(), color);
+            assert_color(Lab::from_color(&color).to_color(), color);
+            assert_color(Oklab::from_color(&color).to_color(), color);
+            assert_color(Oklch::from_color(&color).to_color(), color);
+        }
+    }
+
+    #[test]
+    fn hsl_and_hsv_reference_values() {
+        let hsl = Hsl::from_color(&Color { r: 0.0, g: 0.5, b: 1.0, a: 1.0 });
+        assert_close(hsl.h, 210.0, 0.01);
+        assert_close(hsl.s, 1.0, 0.001);
+        assert_close(hsl.l, 0.5, 0.001);
+        let hsv = Hsv::from_color(&Color { r: 0.5, g: 0.25, b: 0.5, a: 1.0 });
+        assert_close(hsv.h, 300.0, 0.01);
+        assert_close(hsv.s, 0.5, 0.001);
+        assert_close(hsv.v, 0.5, 0.001);
+    }
+
+    /// CIELAB with the D65 white point, as computed from the sRGB primaries
+    #[test]
+    fn lab_reference_values() {
+        let cases = [
+            (Color::RED, (53.2408, 80.0925, 67.2032)),
+            (Color::GREEN, (87.7347, -86.1827, 83.1793)),
+            (Color::BLUE, (32.2970, 79.1875, -107.8602)),
+            (Color::WHITE, (100.0, 0.0, 0.0)),
+        ];
+        for (color, (l, a, b)) in cases.iter() {
+            let lab = Lab::from_color(color);
+            assert_close(lab.l, *l, 0.01);
+            assert_close(lab.a, *a, 0.01);
+            assert_close(lab.b, *b, 0.01);
+        }
+    }
+
+    /// The sRGB primaries in the examples of CSS Color 4 and Ottosson's Oklab post
+    #[test]
+    fn oklab_reference_values() {
+        let cases = [
+            (Color::RED, (0.62796, 0.22486, 0.12585)),
+            (Color::GREEN, (0.86644, -0.23389, 0.17950)),
+            (Color::BLUE, (0.45201, -0.03246, -0.31153)),
+            (Color::WHITE, (1.0, 0.0, 0.0)),
+            (Color::BLACK, (0.0, 0.0, 0.0)),
+        ];
+        for (color, (l, a, b)) in cases.iter() {
+            let lab = Oklab::from_color(color);
+            assert_close(lab.l, *l, 0.0005);
+            assert_close(lab.a, *a, 0.0005);
+            assert_close(lab.b, *b, 0.0005);
+        }
+        // oklch(62.8% 0.2577 29.23) in CSS Color 4
+        let lch = Oklch::from_color(&Color::RED);
+        assert_close(lch.l, 0.62796, 0.0005);
+        assert_close(lch.c, 0.25768, 0.0005);
+        assert_close(lch.h, 29.2339, 0.05);
+    }
+
+    #[test]
+    fn oklch_reduces_chroma_to_stay_in_gamut() {
+        let wanted = Oklch { l: 0.7, c: 0.4, h: 150.0, alpha: 1.0 };
+        let color = wanted.to_color();
+        assert!(is_in_gamut(&color));
+        let result = Oklch::from_color(&color);
+        assert_close(result.l, 0.7, 0.005);
+        assert_close(result.h, 150.0, 1.0);
+        assert!(result.c < 0.4 && result.c > 0.1, "chroma {}", result.c);
+        // Lightness outside 0 to 1 is clamped
+        assert_color(Oklch { l: 1.5, c: 0.1, h: 40.0, alpha: 1.0 }.to_color(), Color::WHITE);
+        assert_color(Oklch { l: -0.5, c: 0.1, h: 40.0, alpha: 1.0 }.to_color(), Color::BLACK);
+    }
+
+    #[test]
+    fn lighten_changes_oklch_lightness() {
+        let color = Color { r: 0.165, g: 0.635, b: 0.596, a: 1.0 };
+        let before = Oklch::from_color(&color);
+        let after = Oklch::from_color(&lighten(&color, 0.1));
+        assert_close(after.l, before.l + 0.1, 0.005);
+        assert_close(after.h, before.h, 1.0);
+        let darker = Oklch::from_color(&lighten(&color, -0.2));
+        assert_close(darker.l, before.l - 0.2, 0.005);
+        assert_color(lighten(&Color::WHITE, 0.1), Color::WHITE);
+        assert_color(lighten(&Color::BLACK, -0.1), Color::BLACK);
+    }
+
+    #[test]
+    fn saturate_scales_chroma() {
+        let color = Color { r: 0.6, g: 0.4, b: 0.3, a: 1.0 };
+        let before = Oklch::from_color(&color);
+        let after = Oklch::from_color(&saturate(&color, 0.2));
+        assert_close(after.c, before.c * 1.2, 0.002);
+        assert!(is_gray(saturate(&color, -1.0)));
+        assert!(is_gray(saturate(&Color { r: 0.3, g: 0.3, b: 0.3, a: 1.0 }, 0.5)));
+    }
+
+    #[test]
+    fn mix_blends_in_oklab() {
+        let (a, b) = (Color::RED, Color::BLUE);
+        assert_color(mix(&a, &b, 0.0), a);
+        assert_color(mix(&a, &b, 1.0), b);
+        assert_color(mix(&a, &b, 2.0), b);
+        let middle = mix(&Color::BLACK, &Color::WHITE, 0.5);
+        assert!(is_gray(middle));
+        assert_close(Oklab::from_color(&middle).l, 0.5, 0.001);
+        let faded = mix(&Color { a: 0.0, ..a }, &a, 0.25);
+        assert_close(faded.a, 0.25, 0.001);
+    }
+
+    #[test]
+    fn mix_srgb_blends_channels() {
+        let middle = mix_srgb(&Color::BLACK, &Color::WHITE, 0.5);
+        assert_color(middle, Color { r: 0.5, g: 0.5, b: 0.5, a: 1.0 });
+        assert_color(mix_srgb(&Color::RED, &Color::BLUE, 0.25), Color { r: 0.75, g: 0.0, b: 0.25, a: 1.0 });
+    }
+}
@@ -0,0 +1,307 @@
+/// WCAG 2.x contrast checks for the color pairs that Tweek controls draw with. Each pair is graded
+/// for the text size given by the theme font size, so a theme can be checked in the editor or in CI:
+///
+/// let report = contrast_report(&theme);
+/// if !report.passes(ContrastGrade::Aa) { ... }
+///
+use super::*;
+
+use std::fmt;
+
+use quicksilver::graphics::Color;
+use serde::{Serialize, Serializer};
+use tweek::gui::Theme;
+
+/// Text at or above this size in pixels counts as large text. WCAG defines large text as 18pt,
+/// which is 24px.
+pub const LARGE_TEXT_SIZE: f32 = 24.0;
+
+/// What a color pair is used to draw, which decides the contrast it needs
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PairUsage {
+    /// Text on a background. Needs 4.5:1 for AA, or 3:1 for large text.
+    Text,
+    /// A control boundary or indicator, such as a border or text cursor. Needs 3:1.
+    NonText,
+}
+
+/// A foreground and background color field used together by a control
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContrastPair {
+    pub name: &'static str,
+    /// The color field of the foreground. Eg: fg_color
+    pub fg_field: &'static str,
+    /// The color field of the background. Eg: bg_color
+    pub bg_field: &'static str,
+    pub usage: PairUsage,
+}
+
+impl ContrastPair {
+    const fn new(name: &'static str, fg_field: &'static str, bg_field: &'static str, usage: PairUsage) -> Self {
+        ContrastPair { name, fg_field, bg_field, usage }
+    }
+}
+
+/// The color pairs drawn by the controls in the Tweek gui. ListBox rows draw their text with the same
+/// colors as other text, so they are covered by the Text pair.
+pub const CONTRAST_PAIRS: &[ContrastPair] = &[
+    ContrastPair::new("Text", "fg_color", "bg_color", PairUsage::Text),
+    ContrastPair::new("Button text", "button_fg_color", "button_bg_color", PairUsage::Text),
+    ContrastPair::new("Pressed button text", "button_fg_color", "bg_pressed_color", PairUsage::Text),
+    ContrastPair::new("Pressed row text", "fg_color", "bg_pressed_color", PairUsage::Text),
+    ContrastPair::new("Highlighted text", "fg_highlight_color", "bg_color", PairUsage::Text),
+    ContrastPair::new("Text field cursor", "cursor_color", "bg_color", PairUsage::NonText),
+    ContrastPair::new("Border", "border_color", "bg_color", PairUsage::NonText),
+];
+
+/// The WCAG conformance level reached by a color pair
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+pub enum ContrastGrade {
+    #[serde(rename = "fail")]
+    Fail,
+    #[serde(rename = "AA")]
+    Aa,
+    #[serde(rename = "AAA")]
+    Aaa,
+}
+
+impl ContrastGrade {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "aa" => Some(ContrastGrade::Aa),
+            "aaa" => Some(ContrastGrade::Aaa),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ContrastGrade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContrastGrade::Fail => write!(f, "fail"),
+            ContrastGrade::Aa => write!(f, "AA"),
+            ContrastGrade::Aaa => write!(f, "AAA"),
+        }
+    }
+}
+
+/// The contrast of one color pair
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContrastCheck {
+    pub name: &'static str,
+    pub fg_field: &'static str,
+    pub bg_field: &'static str,
+    #[serde(serialize_with = "serialize_hex")]
+    pub fg: Color,
+    #[serde(serialize_with = "serialize_hex")]
+    pub bg: Color,
+    pub usage: PairUsage,
+    /// The contrast ratio from 1 to 21
+    pub ratio: f32,
+    /// The grade for normal size text
+    pub normal: ContrastGrade,
+    /// The grade for large text
+    pub large: ContrastGrade,
+    /// The grade for the theme font size
+    pub grade: ContrastGrade,
+}
+
+impl ContrastCheck {
+    /// The ratio needed for a grade at the text size used by this check
+    pub fn required_ratio(&self, grade: ContrastGrade, large_text: bool) -> f32 {
+        required_ratio(self.usage, grade, large_text)
+    }
+}
+
+/// The contrast of every color pair in a theme
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContrastReport {
+    pub font_size: f32,
+    pub large_text: bool,
+    pub checks: Vec<ContrastCheck>,
+}
+
+impl ContrastReport {
+    /// The checks which do not reach the grade
+    pub fn failures(&self, grade: ContrastGrade) -> Vec<&ContrastCheck> {
+        self.checks.iter().filter(|check| check.grade < grade).collect()
+    }
+
+    /// True if every pair reaches the grade. Use this to fail CI on regressions.
+    pub fn passes(&self, grade: ContrastGrade) -> bool {
+        self.failures(grade).is_empty()
+    }
+
+    /// One line per check which does not reach the grade. Eg: "Border 1.3:1, needs 3:1 for AA"
+    pub fn warnings(&self, grade: ContrastGrade) -> Vec<String> {
+        self.failures(grade)
+            .iter()
+            .map(|check| {
+                let needed = check.required_ratio(grade, self.large_text);
+                format!("{} {:.1}:1, needs {}:1 for {}", check.name, check.ratio, needed, grade)
+            })
+            .collect()
+    }
+}
+
+/// Check the contrast of every pair in CONTRAST_PAIRS. Text pairs are graded as large text if the
+/// theme font size is at least LARGE_TEXT_SIZE.
+pub fn contrast_report(theme: &Theme) -> ContrastReport {
+    let large_text = theme.font_size >= LARGE_TEXT_SIZE;
+    let checks = CONTRAST_PAIRS
+        .iter()
+        .filter(|pair| pair.fg_field != "border_color" || theme.border_width > 0.0)
+        .filter_map(|pair| {
+            let fg = theme_color(theme, pair.fg_field)?;
+            let bg = theme_color(theme, pair.bg_field)?;
+            let ratio = contrast_ratio(&fg, &bg);
+            let normal = grade_ratio(ratio, pair.usage, false);
+            let large = grade_ratio(ratio, pair.usage, true);
+            let grade = if large_text { large } else { normal };
+            let check = ContrastCheck {
+                name: pair.name,
+                fg_field: pair.fg_field,
+                bg_field: pair.bg_field,
+                fg,
+                bg,
+                usage: pair.usage,
+                ratio,
+                normal,
+                large,
+                grade,
+            };
+            Some(check)
+        })
+        .collect();
+    ContrastReport { font_size: theme.font_size, large_text, checks }
+}
+
+/// The WCAG relative luminance of a color, ignoring alpha
+pub fn relative_luminance(color: &Color) -> f32 {
+    0.2126 * srgb_to_linear(color.r) + 0.7152 * srgb_to_linear(color.g) + 0.0722 * srgb_to_linear(color.b)
+}
+
+/// The WCAG contrast ratio of a foreground drawn on a background, from 1 to 21. A translucent
+/// foreground is blended with the background first.
+pub fn contrast_ratio(fg: &Color, bg: &Color) -> f32 {
+    let fg = Color { a: 1.0, ..mix_srgb(bg, fg, fg.a) };
+    let l1 = relative_luminance(&fg);
+    let l2 = relative_luminance(bg);
+    (l1.max(l2) + 0.05) / (l1.min(l2) + 0.05)
+}
+
+/// The ratio a pair needs to reach a grade
+pub fn required_ratio(usage: PairUsage, grade: ContrastGrade, large_text: bool) -> f32 {
+    match (usage, grade, large_text) {
+        (_, ContrastGrade::Fail, _) => 1.0,
+        (PairUsage::NonText, _, _) => 3.0,
+        (PairUsage::Text, ContrastGrade::Aa, true) => 3.0,
+        (PairUsage::Text, ContrastGrade::Aa, false) => 4.5,
+        (PairUsage::Text, ContrastGrade::Aaa, true) => 4.5,
+        (PairUsage::Text, ContrastGrade::Aaa, false) => 7.0,
+    }
+}
+
+fn grade_ratio(ratio: f32, usage: PairUsage, large_text: bool) -> ContrastGrade {
+    if ratio >= required_ratio(usage, ContrastGrade::Aaa, large_text) {
+        ContrastGrade::Aaa
+    } else if ratio >= required_ratio(usage, ContrastGrade::Aa, large_text) {
+        ContrastGrade::Aa
+    } else {
+        ContrastGrade::Fail
+    }
+}
+
+fn theme_color(theme: &Theme, field: &str) -> Option<Color> {
+    let color = match field {
+        "bg_color" => theme.bg_color,
+        "fg_color" => theme.fg_color,
+        "border_color" => theme.border_color,
+        "button_bg_color" => theme.button_bg_color,
+        "button_fg_color" => theme.button_fg_color,
+        "bg_pressed_color" => theme.bg_pressed_color,
+        "fg_highlight_color" => theme.fg_highlight_color,
+        "cursor_color" => theme.cursor_color,
+        _ => return None,
+    };
+    Some(color)
+}
+
+fn serialize_hex<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex_string(color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_ratio(fg: &str, bg: &str, expected: f32) {
+        let ratio = contrast_ratio(&parse_hex_color(fg).unwrap(), &parse_hex_color(bg).unwrap());
+        assert!((ratio - expected).abs() < 0.01, "{} on {} is {}:1, expected {}:1", fg, bg, ratio, expected);
+    }
+
+    /// A report for a theme that draws text in fg on a white background
+    fn text_report(fg: &str, font_size: f32) -> ContrastReport {
+        let mut document = builtin_themes().remove(0);
+        document.font_size = font_size;
+        document.colors.fg_color = parse_hex_color(fg).unwrap();
+        document.colors.bg_color = Color::WHITE;
+        contrast_report(&document.to_theme())
+    }
+
+    fn text_check(report: &ContrastReport) -> &ContrastCheck {
+        report.checks.iter().find(|check| check.name == "Text").unwrap()
+    }
+
+    #[test]
+    fn reference_ratios() {
+        assert_ratio("#000000", "#FFFFFF", 21.0);
+        assert_ratio("#FFFFFF", "#000000", 21.0);
+        assert_ratio("#FFFFFF", "#FFFFFF", 1.0);
+        assert_ratio("#767676", "#FFFFFF", 4.54);
+        assert_ratio("#777777", "#FFFFFF", 4.48);
+        assert_ratio("#949494", "#FFFFFF", 3.03);
+    }
+
+    #[test]
+    fn grade_boundaries() {
+        assert_eq!(grade_ratio(4.5, PairUsage::Text, false), ContrastGrade::Aa);
+        assert_eq!(grade_ratio(4.49, PairUsage::Text, false), ContrastGrade::Fail);
+        assert_eq!(grade_ratio(7.0, PairUsage::Text, false), ContrastGrade::Aaa);
+        assert_eq!(grade_ratio(3.0, PairUsage::Text, true), ContrastGrade::Aa);
+        assert_eq!(grade_ratio(2.99, PairUsage::Text, true), ContrastGrade::Fail);
+        assert_eq!(grade_ratio(4.5, PairUsage::Text, true), ContrastGrade::Aaa);
+        assert_eq!(grade_ratio(3.0, PairUsage::NonText, false), ContrastGrade::Aaa);
+        assert_eq!(grade_ratio(2.99, PairUsage::NonText, false), ContrastGrade::Fail);
+    }
+
+    #[test]
+    fn text_pairs_at_the_aa_boundary() {
+        let report = text_report("#767676", 16.0);
+        assert!(!report.large_text);
+        assert_eq!(text_check(&report).grade, ContrastGrade::Aa);
+        let report = text_report("#777777", 16.0);
+        assert_eq!(text_check(&report).grade, ContrastGrade::Fail);
+        assert_eq!(text_check(&report).large, ContrastGrade::Aa);
+    }
+
+    #[test]
+    fn large_text_passes_at_three() {
+        let report = text_report("#949494", LARGE_TEXT_SIZE);
+        assert!(report.large_text);
+        let check = text_check(&report);
+        assert_eq!(check.normal, ContrastGrade::Fail);
+        assert_eq!(check.grade, ContrastGrade::Aa);
+        let report = text_report("#949494", LARGE_TEXT_SIZE - 1.0);
+        assert_eq!(text_check(&report).grade, ContrastGrade::Fail);
+    }
+
+    #[test]
+    fn pairs_are_unique() {
+        for (index, pair) in CONTRAST_PAIRS.iter().enumerate() {
+            let same = |other: &&ContrastPair| other.fg_field == pair.fg_field && other.bg_field == pair.bg_field;
+            assert_eq!(CONTRAST_PAIRS[..index].iter().find(same), None, "{} repeats a pair", pair.name);
+        }
+    }
+}
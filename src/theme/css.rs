@@ -0,0 +1,126 @@
+/// Export of themes as CSS custom properties and SCSS variables, so web pages can share the palette
+/// of a Tweek app. Each theme field becomes one variable named from a prefix and the field name.
+/// Eg: with the default options, bg_color becomes --tweek-bg-color in CSS and $tweek-bg-color in SCSS.
+///
+use super::*;
+
+use std::path::Path;
+
+/// How variable names are joined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NameCase {
+    /// tweek-bg-color
+    Kebab,
+    /// tweek_bg_color
+    Snake,
+    /// tweekBgColor
+    Camel,
+}
+
+impl NameCase {
+    /// Parse the name of a case, as used on the command line
+    pub fn from_name(name: &str) -> Option<NameCase> {
+        match name {
+            "kebab" => Some(NameCase::Kebab),
+            "snake" => Some(NameCase::Snake),
+            "camel" => Some(NameCase::Camel),
+            _ => None,
+        }
+    }
+}
+
+/// Options for CSS and SCSS export
+#[derive(Debug, Clone, PartialEq)]
+pub struct CssOptions {
+    /// Prepended to every variable name. May be empty.
+    pub prefix: String,
+    pub case: NameCase,
+}
+
+impl Default for CssOptions {
+    fn default() -> Self {
+        CssOptions { prefix: "tweek".to_string(), case: NameCase::Kebab }
+    }
+}
+
+impl CssOptions {
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_case(mut self, case: NameCase) -> Self {
+        self.case = case;
+        self
+    }
+
+    /// The variable name for a theme field, without the -- or $ sigil
+    pub fn variable_name(&self, field: &str) -> String {
+        let prefix = self.prefix.split(|c: char| c == '_' || c == '-');
+        let parts: Vec<&str> = prefix.chain(field.split('_')).filter(|s| !s.is_empty()).collect();
+        match self.case {
+            NameCase::Kebab => parts.join("-"),
+            NameCase::Snake => parts.join("_"),
+            NameCase::Camel => {
+                let mut name = String::new();
+                for (i, part) in parts.iter().enumerate() {
+                    let mut chars = part.chars();
+                    if let Some(first) = chars.next() {
+                        if i == 0 {
+                            name.extend(first.to_lowercase());
+                        } else {
+                            name.extend(first.to_uppercase());
+                        }
+                        name.push_str(chars.as_str());
+                    }
+                }
+                name
+            }
+        }
+    }
+}
+
+/// The theme fields and their CSS values, in file order
+pub fn css_values(document: &ThemeDocument) -> Vec<(String, String)> {
+    let mut values: Vec<(String, String)> = Vec::new();
+    for field in ThemeColors::FIELDS.iter() {
+        if let Some(color) = document.colors.get(field) {
+            values.push((field.to_string(), hex_string(&color)));
+        }
+    }
+    values.push(("font_size".to_string(), format!("{}px", document.font_size)));
+    values.push(("font_default".to_string(), font_family(&document.fonts.default)));
+    values.push(("font_title".to_string(), font_family(&document.fonts.title)));
+    let style = match document.border.style {
+        BorderKind::None => "none",
+        BorderKind::Solid => "solid",
+    };
+    values.push(("border_style".to_string(), style.to_string()));
+    values.push(("border_width".to_string(), format!("{}px", document.border.width)));
+    values
+}
+
+/// A stylesheet which declares every theme field as a custom property on :root
+pub fn export_css(document: &ThemeDocument, options: &CssOptions) -> String {
+    let mut css = format!("/* {} */\n:root {{\n", document.name);
+    for (field, value) in css_values(document) {
+        css.push_str(&format!("  --{}: {};\n", options.variable_name(&field), value));
+    }
+    css.push_str("}\n");
+    css
+}
+
+/// An SCSS file which declares every theme field as a variable
+pub fn export_scss(document: &ThemeDocument, options: &CssOptions) -> String {
+    let mut scss = format!("// {}\n", document.name);
+    for (field, value) in css_values(document) {
+        scss.push_str(&format!("${}: {};\n", options.variable_name(&field), value));
+    }
+    scss
+}
+
+/// A quoted font family name from a font file name. Eg: "Roboto-Regular.ttf" becomes "\"Roboto-Regular\""
+fn font_family(file_name: &str) -> String {
+    let stem = Path::new(file_name).file_stem().map_or(file_name.to_string(), |s| s.to_string_lossy().to_string());
+    format!("\"{}\"", stem)
+}
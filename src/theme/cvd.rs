@@ -0,0 +1,172 @@
+/// Color vision deficiency simulation. The dichromacy matrices are from Machado, Oliveira and
+/// Fernandes (2009) at full severity and are applied in linear RGB. Achromatopsia is simulated as
+/// the relative luminance of the color.
+///
+use super::*;
+
+use std::fmt;
+
+use quicksilver::graphics::Color;
+
+/// A kind of color vision deficiency to simulate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CvdKind {
+    /// No red cones
+    Protanopia,
+    /// No green cones
+    Deuteranopia,
+    /// No blue cones
+    Tritanopia,
+    /// No color vision
+    Achromatopsia,
+}
+
+impl CvdKind {
+    pub const ALL: [CvdKind; 4] =
+        [CvdKind::Protanopia, CvdKind::Deuteranopia, CvdKind::Tritanopia, CvdKind::Achromatopsia];
+
+    /// The matrix applied to linear RGB, by rows
+    pub fn matrix(&self) -> [[f32; 3]; 3] {
+        match self {
+            CvdKind::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            CvdKind::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            CvdKind::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+            CvdKind::Achromatopsia => [[0.2126, 0.7152, 0.0722], [0.2126, 0.7152, 0.0722], [0.2126, 0.7152, 0.0722]],
+        }
+    }
+}
+
+impl fmt::Display for CvdKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CvdKind::Protanopia => write!(f, "Protanopia"),
+            CvdKind::Deuteranopia => write!(f, "Deuteranopia"),
+            CvdKind::Tritanopia => write!(f, "Tritanopia"),
+            CvdKind::Achromatopsia => write!(f, "Achromatopsia"),
+        }
+    }
+}
+
+/// The color as seen with a color vision deficiency. Alpha is unchanged.
+pub fn simulate_cvd(color: &Color, kind: CvdKind) -> Color {
+    let linear = [srgb_to_linear(color.r), srgb_to_linear(color.g), srgb_to_linear(color.b)];
+    let m = kind.matrix();
+    let channel = |row: [f32; 3]| {
+        let value = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+        linear_to_srgb(value.max(0.0).min(1.0))
+    };
+    Color { r: channel(m[0]), g: channel(m[1]), b: channel(m[2]), a: color.a }
+}
+
+impl ThemeDocument {
+    /// A copy of the document with every color, including state colors, as seen with a color vision
+    /// deficiency
+    pub fn simulate_cvd(&self, kind: CvdKind) -> ThemeDocument {
+        let mut document = self.clone();
+        for field in ThemeColors::FIELDS.iter() {
+            if let Some(color) = self.colors.get(field) {
+                document.colors.set(field, simulate_cvd(&color, kind));
+            }
+        }
+        for state in ControlState::ALL.iter() {
+            if let Some(colors) = document.states.get_mut(*state) {
+                for field in StateColors::FIELDS.iter() {
+                    if let Some(color) = colors.get(field) {
+                        colors.set(field, Some(simulate_cvd(&color, kind)));
+                    }
+                }
+            }
+        }
+        document
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r: r as f32 / 255.0, g: g as f32 / 255.0, b: b as f32 / 255.0, a: 1.0 }
+    }
+
+    /// Compare as 8 bit channels, allowing one step of rounding
+    fn assert_rgb(actual: Color, expected: Color) {
+        let byte = |v: f32| (v * 255.0).round() as i32;
+        let channels = |c: Color| [byte(c.r), byte(c.g), byte(c.b)];
+        let (a, e) = (channels(actual), channels(expected));
+        assert!(a.iter().zip(e.iter()).all(|(a, e)| (a - e).abs() <= 1), "{:?} != {:?}", a, e);
+    }
+
+    #[test]
+    fn white_and_black_are_unchanged() {
+        for kind in CvdKind::ALL.iter() {
+            assert_rgb(simulate_cvd(&Color::WHITE, *kind), Color::WHITE);
+            assert_rgb(simulate_cvd(&Color::BLACK, *kind), Color::BLACK);
+        }
+    }
+
+    #[test]
+    fn alpha_is_unchanged() {
+        let color = Color { a: 0.25, ..rgb(200, 40, 90) };
+        for kind in CvdKind::ALL.iter() {
+            assert_eq!(simulate_cvd(&color, *kind).a, 0.25);
+        }
+    }
+
+    #[test]
+    fn achromatopsia_is_gray() {
+        for color in [rgb(255, 0, 0), rgb(30, 144, 255), rgb(250, 200, 20)].iter() {
+            let gray = simulate_cvd(color, CvdKind::Achromatopsia);
+            assert!((gray.r - gray.g).abs() < 1e-6 && (gray.g - gray.b).abs() < 1e-6, "{:?}", gray);
+        }
+        // The relative luminance of pure green is 0.7152
+        assert_rgb(simulate_cvd(&rgb(0, 255, 0), CvdKind::Achromatopsia), rgb(220, 220, 220));
+    }
+
+    #[test]
+    fn machado_dichromacy() {
+        let cases = [
+            (CvdKind::Protanopia, rgb(255, 0, 0), rgb(109, 95, 0)),
+            (CvdKind::Protanopia, rgb(0, 255, 0), rgb(255, 229, 0)),
+            (CvdKind::Protanopia, rgb(0, 0, 255), rgb(0, 89, 255)),
+            (CvdKind::Deuteranopia, rgb(255, 0, 0), rgb(163, 144, 0)),
+            (CvdKind::Deuteranopia, rgb(0, 255, 0), rgb(239, 214, 58)),
+            (CvdKind::Deuteranopia, rgb(0, 0, 255), rgb(0, 61, 251)),
+            (CvdKind::Tritanopia, rgb(255, 0, 0), rgb(255, 0, 15)),
+            (CvdKind::Tritanopia, rgb(0, 255, 0), rgb(0, 247, 217)),
+            (CvdKind::Tritanopia, rgb(0, 0, 255), rgb(0, 107, 150)),
+        ];
+        for (kind, color, expected) in cases.iter() {
+            assert_rgb(simulate_cvd(color, *kind), *expected);
+        }
+    }
+
+    #[test]
+    fn document_states_are_simulated() {
+        let document = builtin_themes().remove(0);
+        let simulated = document.simulate_cvd(CvdKind::Achromatopsia);
+        let bg = simulated.colors.bg_color;
+        assert!((bg.r - bg.g).abs() < 1e-6 && (bg.g - bg.b).abs() < 1e-6);
+        for state in ControlState::ALL.iter() {
+            if let Some(colors) = simulated.states.get(*state) {
+                for field in StateColors::FIELDS.iter() {
+                    if let Some(color) = colors.get(field) {
+                        assert!((color.r - color.b).abs() < 1e-6, "{} {:?}", field, state);
+                    }
+                }
+            }
+        }
+    }
+}
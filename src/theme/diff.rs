@@ -0,0 +1,321 @@
+/// Structural diff and three-way merge of themes. Both work on flattened field paths, where nested
+/// objects are joined with dots. Eg: "colors.bg_color", "expressions.border_color", "states.hover.fg_color".
+///
+/// A diff compares the resolved values of two themes, so a derived theme is compared by what it
+/// looks like. A merge works on the contents of theme files, so a derived theme stays derived:
+///
+/// let merge = merge_theme_values(&base, &ours, &theirs);
+/// if merge.conflicts.is_empty() { fs::write(path, serde_json::to_string_pretty(&merge.merged)?)?; }
+///
+use super::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// How a field differs between two themes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// One field that differs between two themes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    /// The flattened field path. Eg: colors.bg_color
+    pub path: String,
+    pub kind: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+    /// For changed colors, the perceptual distance in Oklab (see color_distance). Below about 0.02 the
+    /// change is hard to see.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_distance: Option<f32>,
+}
+
+/// The differences between two themes, sorted by field path
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ThemeDiff {
+    pub changes: Vec<FieldChange>,
+}
+
+impl ThemeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// A field that both sides of a merge changed in different ways
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MergeConflict {
+    pub path: String,
+    /// The value in the common ancestor. None if the field was not there.
+    pub base: Option<Value>,
+    /// The value on our side. None if we removed the field.
+    pub ours: Option<Value>,
+    /// The value on their side. None if they removed the field.
+    pub theirs: Option<Value>,
+}
+
+/// The result of a three-way merge
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThemeMerge {
+    /// The merged theme file contents. Conflicting fields keep our value.
+    pub merged: Value,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl ThemeMerge {
+    /// Validate and parse the merged theme file contents
+    pub fn to_source(&self) -> Result<ThemeSource, ThemeError> {
+        let json = serde_json::to_string_pretty(&self.merged)?;
+        ThemeSource::from_json(&json)
+    }
+}
+
+/// Compare two themes field by field
+pub fn diff_themes(before: &ThemeDocument, after: &ThemeDocument) -> Result<ThemeDiff, ThemeError> {
+    let before = flatten(&serde_json::to_value(before)?);
+    let after = flatten(&serde_json::to_value(after)?);
+    Ok(diff_values(&before, &after))
+}
+
+/// Merge the changes that ours and theirs made to a common ancestor. Themes are merged in their file
+/// form, so a derived theme is merged by the fields it overrides.
+pub fn merge_themes(
+    base: &ThemeDocument,
+    ours: &ThemeDocument,
+    theirs: &ThemeDocument,
+) -> Result<ThemeMerge, ThemeError> {
+    let file_value = |document: &ThemeDocument| -> Result<Value, ThemeError> {
+        Ok(serde_json::from_str(&document.to_json()?)?)
+    };
+    Ok(merge_theme_values(&file_value(base)?, &file_value(ours)?, &file_value(theirs)?))
+}
+
+/// Merge the contents of three theme files. A field changed on only one side takes that change. A field
+/// changed on both sides to different values is a conflict and keeps our value.
+pub fn merge_theme_values(base: &Value, ours: &Value, theirs: &Value) -> ThemeMerge {
+    let base = flatten(base);
+    let ours = flatten(ours);
+    let theirs = flatten(theirs);
+    let paths: BTreeSet<&String> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+
+    let mut merged: BTreeMap<String, Value> = BTreeMap::new();
+    let mut conflicts: Vec<MergeConflict> = Vec::new();
+    for path in paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+        let value = if o == t || t == b {
+            o
+        } else if o == b {
+            t
+        } else {
+            conflicts.push(MergeConflict {
+                path: path.clone(),
+                base: b.cloned(),
+                ours: o.cloned(),
+                theirs: t.cloned(),
+            });
+            o
+        };
+        if let Some(value) = value {
+            merged.insert(path.clone(), value.clone());
+        }
+    }
+    ThemeMerge { merged: unflatten(&merged), conflicts }
+}
+
+fn diff_values(before: &BTreeMap<String, Value>, after: &BTreeMap<String, Value>) -> ThemeDiff {
+    let paths: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let changes = paths
+        .into_iter()
+        .filter_map(|path| {
+            let (b, a) = (before.get(path), after.get(path));
+            let kind = match (b, a) {
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                (Some(b), Some(a)) if b != a => ChangeKind::Changed,
+                _ => return None,
+            };
+            let color = |value: Option<&Value>| value.and_then(|value| value.as_str()).and_then(parse_hex_color);
+            let color_distance = match (color(b), color(a)) {
+                (Some(b), Some(a)) if is_color_path(path) => Some(color_distance(&b, &a)),
+                _ => None,
+            };
+            Some(FieldChange { path: path.clone(), kind, before: b.cloned(), after: a.cloned(), color_distance })
+        })
+        .collect();
+    ThemeDiff { changes }
+}
+
+/// True for paths of color values. Expressions are text, even though they are keyed by color name.
+fn is_color_path(path: &str) -> bool {
+    path.ends_with("_color") && !path.starts_with(EXPRESSION_FIELD_PREFIX)
+}
+
+/// Flatten nested objects into dotted paths. Other values, including arrays, are leaves.
+fn flatten(value: &Value) -> BTreeMap<String, Value> {
+    fn visit(value: &Value, prefix: &str, result: &mut BTreeMap<String, Value>) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                    visit(value, &path, result);
+                }
+            }
+            _ => {
+                result.insert(prefix.to_string(), value.clone());
+            }
+        }
+    }
+    let mut result = BTreeMap::new();
+    visit(value, "", &mut result);
+    result
+}
+
+/// The reverse of flatten
+fn unflatten(values: &BTreeMap<String, Value>) -> Value {
+    fn insert(object: &mut Map<String, Value>, keys: &[&str], value: Value) {
+        if let Some((first, rest)) = keys.split_first() {
+            if rest.is_empty() {
+                object.insert(first.to_string(), value);
+            } else if let Value::Object(child) = object.entry(*first).or_insert_with(|| Value::Object(Map::new())) {
+                insert(child, rest, value);
+            }
+        }
+    }
+    let mut root = Map::new();
+    for (path, value) in values {
+        let keys: Vec<&str> = path.split('.').collect();
+        insert(&mut root, &keys, value.clone());
+    }
+    Value::Object(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use quicksilver::graphics::Color;
+    use serde_json::json;
+
+    #[test]
+    fn flatten_and_unflatten() {
+        let value = json!({
+            "id": 101,
+            "colors": { "bg_color": "#101820", "fg_color": "#FFFFFF" },
+            "states": { "hover": { "bg_color": "#202830" } },
+            "fonts": ["a.ttf", "b.ttf"]
+        });
+        let flat = flatten(&value);
+        let paths: Vec<&str> = flat.keys().map(|path| path.as_str()).collect();
+        assert_eq!(paths, vec!["colors.bg_color", "colors.fg_color", "fonts", "id", "states.hover.bg_color"]);
+        assert_eq!(flat["states.hover.bg_color"], json!("#202830"));
+        assert_eq!(flat["fonts"], json!(["a.ttf", "b.ttf"]));
+        assert_eq!(unflatten(&flat), value);
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed() {
+        let before = flatten(&json!({ "colors": { "bg_color": "#000000", "fg_color": "#FFFFFF" }, "name": "A" }));
+        let after = flatten(&json!({ "colors": { "bg_color": "#FFFFFF" }, "name": "A", "extends": "dark-theme" }));
+        let diff = diff_values(&before, &after);
+        let kinds: Vec<(&str, ChangeKind)> = diff.changes.iter().map(|c| (c.path.as_str(), c.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("colors.bg_color", ChangeKind::Changed),
+                ("colors.fg_color", ChangeKind::Removed),
+                ("extends", ChangeKind::Added),
+            ]
+        );
+        let distance = diff.changes[0].color_distance.unwrap();
+        assert!((distance - 1.0).abs() < 0.001, "{}", distance);
+        assert_eq!(diff.changes[1].color_distance, None);
+        assert!(diff_values(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn expressions_are_not_colors() {
+        let before = flatten(&json!({ "expressions": { "border_color": "#000000" } }));
+        let after = flatten(&json!({ "expressions": { "border_color": "#FFFFFF" } }));
+        assert_eq!(diff_values(&before, &after).changes[0].color_distance, None);
+    }
+
+    #[test]
+    fn diff_themes_compares_resolved_values() {
+        let before = builtin_themes().remove(0);
+        let mut after = before.clone();
+        after.colors.bg_color = Color::RED;
+        after.font_size += 2.0;
+        let diff = diff_themes(&before, &after).unwrap();
+        let paths: Vec<&str> = diff.changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec!["colors.bg_color", "font_size"]);
+        assert!(diff.changes[0].color_distance.is_some());
+    }
+
+    #[test]
+    fn merge_takes_one_sided_changes() {
+        let base = json!({ "name": "A", "colors": { "bg_color": "#000000", "fg_color": "#FFFFFF" } });
+        let ours = json!({ "name": "B", "colors": { "bg_color": "#000000", "fg_color": "#FFFFFF" } });
+        let theirs = json!({ "name": "A", "colors": { "bg_color": "#111111" }, "font_size": 16.0 });
+        let merge = merge_theme_values(&base, &ours, &theirs);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.merged, json!({ "name": "B", "colors": { "bg_color": "#111111" }, "font_size": 16.0 }));
+    }
+
+    #[test]
+    fn merge_same_change_on_both_sides() {
+        let base = json!({ "colors": { "bg_color": "#000000" } });
+        let both = json!({ "colors": { "bg_color": "#222222" } });
+        let merge = merge_theme_values(&base, &both, &both);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.merged, both);
+    }
+
+    #[test]
+    fn merge_conflicts_keep_our_value() {
+        let base = json!({ "colors": { "bg_color": "#000000", "fg_color": "#FFFFFF" }, "font_size": 14.0 });
+        let ours = json!({ "colors": { "bg_color": "#111111" }, "font_size": 14.0 });
+        let theirs = json!({ "colors": { "bg_color": "#222222", "fg_color": "#EEEEEE" }, "font_size": 14.0 });
+        let merge = merge_theme_values(&base, &ours, &theirs);
+        assert_eq!(
+            merge.conflicts,
+            vec![
+                MergeConflict {
+                    path: "colors.bg_color".to_string(),
+                    base: Some(json!("#000000")),
+                    ours: Some(json!("#111111")),
+                    theirs: Some(json!("#222222")),
+                },
+                MergeConflict {
+                    path: "colors.fg_color".to_string(),
+                    base: Some(json!("#FFFFFF")),
+                    ours: None,
+                    theirs: Some(json!("#EEEEEE")),
+                },
+            ]
+        );
+        assert_eq!(merge.merged, json!({ "colors": { "bg_color": "#111111" }, "font_size": 14.0 }));
+    }
+
+    #[test]
+    fn merge_keeps_a_derived_theme_derived() {
+        let base = json!({ "id": 101, "name": "Dusk", "extends": "dark-theme", "colors": { "bg_color": "#101820" } });
+        let ours = json!({ "id": 101, "name": "Dusk", "extends": "dark-theme", "colors": { "bg_color": "#101820" },
+            "font_size": 16.0 });
+        let theirs = json!({ "id": 101, "name": "Dusk", "extends": "dark-theme", "colors": { "bg_color": "#182028" } });
+        let merge = merge_theme_values(&base, &ours, &theirs);
+        assert!(merge.conflicts.is_empty());
+        match merge.to_source().unwrap() {
+            ThemeSource::Derived(derived) => assert_eq!(derived.extends, "dark-theme"),
+            ThemeSource::Complete(document) => panic!("{} is not derived", document.name),
+        }
+    }
+}
@@ -0,0 +1,406 @@
+/// The on-disk JSON format for Tweek themes.
+///
+/// A theme file looks like this. Colors are hex strings (#RRGGBB or #RRGGBBAA) and the font entries are
+/// file names which the host app resolves against its own font directory (static/ in this repo).
+///
+/// {
+///   "version": 1,
+///   "id": 100,
+///   "name": "My theme",
+///   "font_size": 14.0,
+///   "fonts": { "default": "Roboto-Regular.ttf", "title": "Roboto-Bold.ttf" },
+///   "colors": {
+///     "bg_color": "#FBFBFB",
+///     "fg_color": "#403F53",
+///     "border_color": "#D9D9D9",
+///     "button_bg_color": "#2AA298",
+///     "button_fg_color": "#FFFFFF",
+///     "bg_pressed_color": "#E0E7EA",
+///     "fg_highlight_color": "#4876D6",
+///     "cursor_color": "#90A7B2"
+///   },
+///   "border": { "style": "solid", "width": 1.0 }
+/// }
+///
+/// Colors can also be derived from other colors with an "expressions" object. See expr.rs.
+/// The colors of hovered, pressed, focused and disabled controls can be set in a "states" object.
+/// See states.rs.
+///
+use super::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use quicksilver::graphics::Color;
+use serde::{Deserialize, Serialize};
+use tweek::gui::{DrawFont, Theme, ThemeBuilder, DARK_THEME, LIGHT_THEME};
+
+/// The current version of the theme file format
+pub const THEME_FORMAT_VERSION: u32 = 1;
+/// The file extension used for theme files
+pub const THEME_FILE_EXT: &str = "json";
+
+/// A serializable description of a Tweek Theme
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeDocument {
+    /// The file format version. Defaults to the current version if missing.
+    #[serde(default = "ThemeDocument::current_version")]
+    pub version: u32,
+    /// The id used when registering the theme in a ThemePicker
+    pub id: u32,
+    /// The display name of the theme
+    pub name: String,
+    /// The default font size
+    pub font_size: f32,
+    pub fonts: ThemeFonts,
+    pub colors: ThemeColors,
+    pub border: ThemeBorder,
+    /// The id or slug of the base theme, for themes that only store the fields they override.
+    /// See ThemeSource for loading these files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// The field paths this theme overrides. The other fields are inherited from the base theme.
+    /// Only used if extends is set.
+    #[serde(skip)]
+    pub overrides: BTreeSet<String>,
+    /// Color expressions keyed by color field name. Eg: "border_color": "darken(fg_color, 10%)".
+    /// The evaluated values are stored in colors.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub expressions: BTreeMap<String, String>,
+    /// Color overrides for interaction states
+    #[serde(default, skip_serializing_if = "ThemeStates::is_empty")]
+    pub states: ThemeStates,
+}
+
+/// Font file names for the theme
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeFonts {
+    /// The font used for body text and controls
+    pub default: String,
+    /// The font used for titles and headers
+    pub title: String,
+}
+
+impl Default for ThemeFonts {
+    fn default() -> Self {
+        ThemeFonts { default: "Roboto-Regular.ttf".to_string(), title: "Roboto-Bold.ttf".to_string() }
+    }
+}
+
+impl ThemeFonts {
+    /// Read the font files from a directory. Eg: static/
+    pub fn load<P: AsRef<Path>>(&self, dir: P) -> Result<FontData, ThemeError> {
+        let dir = dir.as_ref();
+        Ok(FontData { default: fs::read(dir.join(&self.default))?, title: fs::read(dir.join(&self.title))? })
+    }
+}
+
+/// The contents of the font files of a theme, read with ThemeFonts::load
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontData {
+    pub default: Vec<u8>,
+    pub title: Vec<u8>,
+}
+
+/// The colors of a theme. Each field matches the Tweek Theme field of the same name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeColors {
+    #[serde(with = "hex_color")]
+    pub bg_color: Color,
+    #[serde(with = "hex_color")]
+    pub fg_color: Color,
+    #[serde(with = "hex_color")]
+    pub border_color: Color,
+    #[serde(with = "hex_color")]
+    pub button_bg_color: Color,
+    #[serde(with = "hex_color")]
+    pub button_fg_color: Color,
+    #[serde(with = "hex_color")]
+    pub bg_pressed_color: Color,
+    #[serde(with = "hex_color")]
+    pub fg_highlight_color: Color,
+    #[serde(with = "hex_color")]
+    pub cursor_color: Color,
+}
+
+impl ThemeColors {
+    /// The names of all color fields, in file order
+    pub const FIELDS: [&'static str; 8] = [
+        "bg_color",
+        "fg_color",
+        "border_color",
+        "button_bg_color",
+        "button_fg_color",
+        "bg_pressed_color",
+        "fg_highlight_color",
+        "cursor_color",
+    ];
+
+    /// Get a color by field name
+    pub fn get(&self, field: &str) -> Option<Color> {
+        let color = match field {
+            "bg_color" => self.bg_color,
+            "fg_color" => self.fg_color,
+            "border_color" => self.border_color,
+            "button_bg_color" => self.button_bg_color,
+            "button_fg_color" => self.button_fg_color,
+            "bg_pressed_color" => self.bg_pressed_color,
+            "fg_highlight_color" => self.fg_highlight_color,
+            "cursor_color" => self.cursor_color,
+            _ => return None,
+        };
+        Some(color)
+    }
+
+    /// Set a color by field name. Returns false if there is no such field.
+    pub fn set(&mut self, field: &str, color: Color) -> bool {
+        let target = match field {
+            "bg_color" => &mut self.bg_color,
+            "fg_color" => &mut self.fg_color,
+            "border_color" => &mut self.border_color,
+            "button_bg_color" => &mut self.button_bg_color,
+            "button_fg_color" => &mut self.button_fg_color,
+            "bg_pressed_color" => &mut self.bg_pressed_color,
+            "fg_highlight_color" => &mut self.fg_highlight_color,
+            "cursor_color" => &mut self.cursor_color,
+            _ => return false,
+        };
+        *target = color;
+        true
+    }
+}
+
+/// The kind of border drawn around controls
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderKind {
+    None,
+    Solid,
+}
+
+/// The default border style of controls. The border color lives in ThemeColors.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeBorder {
+    pub style: BorderKind,
+    pub width: f32,
+}
+
+impl ThemeDocument {
+    fn current_version() -> u32 {
+        THEME_FORMAT_VERSION
+    }
+
+    /// Create a document from an existing Tweek Theme, such as one made by ThemeBuilder. A Theme holds
+    /// loaded fonts rather than file names, so the document gets the default fonts, which are the Roboto
+    /// files the ThemeBuilder themes use. Use with_fonts for a theme made with other font files.
+    pub fn from_theme(id: u32, name: &str, theme: &Theme) -> Self {
+        let colors = ThemeColors {
+            bg_color: theme.bg_color,
+            fg_color: theme.fg_color,
+            border_color: theme.border_color,
+            button_bg_color: theme.button_bg_color,
+            button_fg_color: theme.button_fg_color,
+            bg_pressed_color: theme.bg_pressed_color,
+            fg_highlight_color: theme.fg_highlight_color,
+            cursor_color: theme.cursor_color,
+        };
+        let style = if theme.border_width > 0.0 { BorderKind::Solid } else { BorderKind::None };
+        ThemeDocument {
+            version: THEME_FORMAT_VERSION,
+            id,
+            name: name.to_string(),
+            font_size: theme.font_size,
+            fonts: ThemeFonts::default(),
+            colors,
+            border: ThemeBorder { style, width: theme.border_width },
+            extends: None,
+            overrides: BTreeSet::new(),
+            expressions: BTreeMap::new(),
+            states: ThemeStates::default(),
+        }
+    }
+
+    /// Builder method to set the font files of the theme
+    pub fn with_fonts(mut self, fonts: ThemeFonts) -> Self {
+        self.fonts = fonts;
+        self
+    }
+
+    /// Build a Tweek Theme from this document without loading fonts, eg: for checking colors. The
+    /// returned theme uses the fonts of Theme::default(). See to_theme_with_fonts.
+    pub fn to_theme(&self) -> Theme {
+        let mut theme = Theme::default();
+        self.apply_to(&mut theme);
+        theme
+    }
+
+    /// Build a Tweek Theme from this document that draws with its font files:
+    ///
+    /// let fonts = document.fonts.load("static")?;
+    /// let theme = document.to_theme_with_fonts(&fonts);
+    pub fn to_theme_with_fonts(&self, fonts: &FontData) -> Theme {
+        let mut theme = Theme::new(&fonts.default);
+        theme.title_font = Some(DrawFont::from_bytes(fonts.title.clone(), None));
+        self.apply_to(&mut theme);
+        theme
+    }
+
+    /// Copy the size, colors and border of this document to a Tweek Theme
+    fn apply_to(&self, theme: &mut Theme) {
+        theme.font_size = self.font_size;
+        theme.bg_color = self.colors.bg_color;
+        theme.fg_color = self.colors.fg_color;
+        theme.border_color = self.colors.border_color;
+        theme.button_bg_color = self.colors.button_bg_color;
+        theme.button_fg_color = self.colors.button_fg_color;
+        theme.bg_pressed_color = self.colors.bg_pressed_color;
+        theme.fg_highlight_color = self.colors.fg_highlight_color;
+        theme.cursor_color = self.colors.cursor_color;
+        theme.border_width = match self.border.style {
+            BorderKind::None => 0.0,
+            BorderKind::Solid => self.border.width,
+        };
+    }
+
+    /// Parse a theme document from a JSON string. The text is validated first so that every problem
+    /// is reported in ThemeError::Validation rather than just the first one. Themes which extend another
+    /// theme cannot be loaded on their own; use ThemeSource instead.
+    pub fn from_json(json: &str) -> Result<Self, ThemeError> {
+        let errors = validate_theme_json(json);
+        if !errors.is_empty() {
+            return Err(ThemeError::Validation(errors));
+        }
+        let mut document: ThemeDocument = serde_json::from_str(json)?;
+        if let Some(base) = &document.extends {
+            return Err(ThemeError::Invalid(format!("Theme extends {:?} and must be resolved with ThemeSource", base)));
+        }
+        document.apply_expressions().map_err(ThemeError::Expression)?;
+        Ok(document)
+    }
+
+    /// Serialize this document as pretty printed JSON. A theme which extends another theme is written
+    /// with only the fields it overrides.
+    pub fn to_json(&self) -> Result<String, ThemeError> {
+        let json = match &self.extends {
+            Some(base) => serde_json::to_string_pretty(&self.overrides_json(base))?,
+            None => serde_json::to_string_pretty(self)?,
+        };
+        Ok(json)
+    }
+
+    /// The file contents of a derived theme: the header fields plus each overridden field
+    fn overrides_json(&self, base: &str) -> serde_json::Value {
+        let mut root = serde_json::Map::new();
+        root.insert("version".to_string(), self.version.into());
+        root.insert("id".to_string(), self.id.into());
+        root.insert("name".to_string(), self.name.clone().into());
+        root.insert("extends".to_string(), base.into());
+        for path in THEME_FIELDS.iter().filter(|path| self.overrides.contains(**path)) {
+            let value = match self.field(path) {
+                Some(value) => value.to_json(),
+                None => continue,
+            };
+            // Field paths have at most one level of nesting. Eg: colors.bg_color
+            match path.find('.') {
+                Some(dot) => {
+                    let group = root.entry(&path[..dot]).or_insert_with(|| serde_json::Map::new().into());
+                    if let Some(group) = group.as_object_mut() {
+                        group.insert(path[dot + 1..].to_string(), value);
+                    }
+                }
+                None => {
+                    root.insert(path.to_string(), value);
+                }
+            }
+        }
+        let expressions: serde_json::Map<String, serde_json::Value> = self
+            .expressions
+            .iter()
+            .filter(|(field, _)| self.overrides.contains(&format!("{}{}", EXPRESSION_FIELD_PREFIX, field)))
+            .map(|(field, text)| (field.clone(), text.clone().into()))
+            .collect();
+        if !expressions.is_empty() {
+            root.insert("expressions".to_string(), expressions.into());
+        }
+        // The states object is overridden as a whole
+        if self.overrides.contains(STATES_FIELD) && !self.states.is_empty() {
+            if let Ok(states) = serde_json::to_value(&self.states) {
+                root.insert(STATES_FIELD.to_string(), states);
+            }
+        }
+        serde_json::Value::Object(root)
+    }
+
+    /// Read a theme file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ThemeError> {
+        let json = fs::read_to_string(path)?;
+        ThemeDocument::from_json(&json)
+    }
+
+    /// Write this theme to a file, replacing any existing file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ThemeError> {
+        let json = self.to_json()?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// A file name derived from the theme name. Eg: "Light theme" becomes "light-theme.json"
+    pub fn file_name(&self) -> String {
+        format!("{}.{}", self.slug(), THEME_FILE_EXT)
+    }
+
+    /// The theme name in lowercase with dashes instead of spaces and symbols, for use in file names
+    /// and identifiers. Eg: "Light theme" becomes "light-theme"
+    pub fn slug(&self) -> String {
+        slugify(&self.name, self.id)
+    }
+}
+
+/// The documents of the themes built into Tweek, which theme files can extend
+pub fn builtin_themes() -> Vec<ThemeDocument> {
+    vec![
+        ThemeDocument::from_theme(LIGHT_THEME, "Light theme", &ThemeBuilder::light_owl()),
+        ThemeDocument::from_theme(DARK_THEME, "Dark theme", &ThemeBuilder::night_owl()),
+    ]
+}
+
+/// Make a slug from a theme name, falling back to the id if the name has no letters or digits
+pub fn slugify(name: &str, id: u32) -> String {
+    let mut slug = String::new();
+    for c in name.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        format!("theme-{}", id)
+    } else {
+        slug.to_string()
+    }
+}
+
+/// List the theme files in a directory, sorted by path. A missing directory is not an error.
+pub fn theme_files<P: AsRef<Path>>(dir: P) -> io::Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files: Vec<PathBuf> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().map_or(false, |ext| ext == THEME_FILE_EXT) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,60 @@
+use super::*;
+
+use std::fmt;
+use std::io;
+
+/// Errors that can occur while reading or writing theme files
+#[derive(Debug)]
+pub enum ThemeError {
+    /// The file could not be read or written
+    Io(io::Error),
+    /// The file contents are not a valid theme document
+    Parse(serde_json::Error),
+    /// The theme document was parsed but refers to something that does not exist
+    Invalid(String),
+    /// The file has one or more problems found by the validator
+    Validation(Vec<ValidationError>),
+    /// Themes that extend each other in a loop. The names are listed in order, ending with the first one.
+    InheritanceCycle(Vec<String>),
+    /// One or more color expressions could not be evaluated
+    Expression(Vec<ExprError>),
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThemeError::Io(err) => write!(f, "Theme file error: {}", err),
+            ThemeError::Parse(err) => write!(f, "Theme parse error: {}", err),
+            ThemeError::Invalid(msg) => write!(f, "Invalid theme: {}", msg),
+            ThemeError::Validation(errors) => {
+                write!(f, "Theme has {} problem(s)", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+            ThemeError::InheritanceCycle(names) => write!(f, "Inheritance cycle: {}", names.join(" -> ")),
+            ThemeError::Expression(errors) => {
+                write!(f, "Theme has {} expression problem(s)", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {}
+
+impl From<io::Error> for ThemeError {
+    fn from(err: io::Error) -> Self {
+        ThemeError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for ThemeError {
+    fn from(err: serde_json::Error) -> Self {
+        ThemeError::Parse(err)
+    }
+}
@@ -0,0 +1,546 @@
+/// Derived colors. A theme can define some of its colors as expressions of other colors in an
+/// "expressions" object, keyed by color field:
+///
+/// "expressions": {
+///   "border_color": "darken(fg_color, 10%)",
+///   "bg_pressed_color": "mix(bg, fg_highlight, 0.2)"
+/// }
+///
+/// References use the color field names, with or without the _color suffix. Colors can also be
+/// written as hex literals. The functions are:
+///
+/// * darken(color, amount) and lighten(color, amount) change the HSL lightness
+/// * saturate(color, amount) and desaturate(color, amount) change the HSL saturation
+/// * mix(color1, color2, weight) blends towards color2 by weight, which defaults to 0.5
+/// * alpha(color, value) replaces the alpha channel
+/// * invert(color) and grayscale(color)
+///
+/// Amounts are numbers from 0 to 1, or percentages. The colors written in "colors" are the last
+/// evaluated values, so a theme file with expressions can still be read by tools that ignore them.
+///
+use super::*;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use quicksilver::graphics::Color;
+
+/// The prefix used in ThemeDocument::overrides for expressions set in a derived theme.
+/// Eg: expressions.border_color
+pub const EXPRESSION_FIELD_PREFIX: &str = "expressions.";
+
+/// The deepest nesting of function calls in an expression. Eg: invert(invert(bg)) has a depth of 2.
+pub const MAX_EXPR_DEPTH: usize = 32;
+
+/// The functions available in expressions
+pub const EXPR_FUNCTIONS: [&str; 8] =
+    ["darken", "lighten", "saturate", "desaturate", "mix", "alpha", "invert", "grayscale"];
+
+/// A parsed color expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Color(Color),
+    Number(f32),
+    /// A reference to a color field, stored as the full field name. Eg: fg_color
+    Ref(String),
+    Call(String, Vec<Expr>),
+}
+
+/// The value of an evaluated expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExprValue {
+    Color(Color),
+    Number(f32),
+}
+
+/// The kind of problem with an expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExprErrorKind {
+    Syntax,
+    UnknownReference,
+    UnknownFunction,
+    BadArguments,
+    /// The expression depends on itself through other expressions
+    Cycle,
+}
+
+/// A problem with the expression of a color field
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    /// The color field whose expression has the problem
+    pub field: String,
+    pub kind: ExprErrorKind,
+    pub message: String,
+}
+
+impl ExprError {
+    fn new(field: &str, kind: ExprErrorKind, message: String) -> Self {
+        ExprError { field: field.to_string(), kind, message }
+    }
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Find the color field for a reference. Accepts the full field name or the name without _color.
+pub fn color_field_name(name: &str) -> Option<&'static str> {
+    let full = format!("{}_color", name);
+    ThemeColors::FIELDS.iter().find(|field| **field == name || **field == full).cloned()
+}
+
+/// Parse an expression. References are checked against the theme color fields.
+pub fn parse_expr(text: &str) -> Result<Expr, (ExprErrorKind, String)> {
+    let mut parser = ExprParser { chars: text.chars().collect(), index: 0, depth: 0 };
+    parser.skip_whitespace();
+    let expr = parser.parse()?;
+    parser.skip_whitespace();
+    if parser.index < parser.chars.len() {
+        return Err(parser.syntax_error("Unexpected trailing characters"));
+    }
+    Ok(expr)
+}
+
+impl Expr {
+    /// The color fields this expression refers to
+    pub fn references(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        self.collect_references(&mut names);
+        names
+    }
+
+    fn collect_references(&self, names: &mut Vec<String>) {
+        match self {
+            Expr::Ref(name) if !names.contains(name) => names.push(name.clone()),
+            Expr::Call(_, args) => args.iter().for_each(|arg| arg.collect_references(names)),
+            _ => {}
+        }
+    }
+
+    /// Evaluate with the given colors, which must include every referenced field
+    pub fn eval(&self, colors: &ThemeColors) -> Result<ExprValue, (ExprErrorKind, String)> {
+        match self {
+            Expr::Color(color) => Ok(ExprValue::Color(*color)),
+            Expr::Number(n) => Ok(ExprValue::Number(*n)),
+            Expr::Ref(name) => match colors.get(name) {
+                Some(color) => Ok(ExprValue::Color(color)),
+                None => Err((ExprErrorKind::UnknownReference, format!("Unknown color {:?}", name))),
+            },
+            Expr::Call(name, args) => {
+                let values = args.iter().map(|arg| arg.eval(colors)).collect::<Result<Vec<ExprValue>, _>>()?;
+                call_function(name, &values)
+            }
+        }
+    }
+}
+
+fn call_function(name: &str, args: &[ExprValue]) -> Result<ExprValue, (ExprErrorKind, String)> {
+    let bad_args = |expected: &str| {
+        let message = format!("{}() expects {}", name, expected);
+        Err((ExprErrorKind::BadArguments, message))
+    };
+    let color = match name {
+        "darken" | "lighten" | "saturate" | "desaturate" | "alpha" => match args {
+            [ExprValue::Color(color), ExprValue::Number(amount)] => {
+                let hsl = Hsl::from_color(color);
+                match name {
+                    "darken" => Hsl { l: hsl.l - amount, ..hsl }.to_color(),
+                    "lighten" => Hsl { l: hsl.l + amount, ..hsl }.to_color(),
+                    "saturate" => Hsl { s: hsl.s + amount, ..hsl }.to_color(),
+                    "desaturate" => Hsl { s: hsl.s - amount, ..hsl }.to_color(),
+                    _ => Color { a: amount.max(0.0).min(1.0), ..*color },
+                }
+            }
+            _ => return bad_args("a color and an amount"),
+        },
+        "mix" => match args {
+            [ExprValue::Color(a), ExprValue::Color(b)] => mix_srgb(a, b, 0.5),
+            [ExprValue::Color(a), ExprValue::Color(b), ExprValue::Number(weight)] => mix_srgb(a, b, *weight),
+            _ => return bad_args("two colors and an optional weight"),
+        },
+        "invert" | "grayscale" => match args {
+            [ExprValue::Color(color)] if name == "invert" => {
+                Color { r: 1.0 - color.r, g: 1.0 - color.g, b: 1.0 - color.b, a: color.a }
+            }
+            [ExprValue::Color(color)] => Hsl { s: 0.0, ..Hsl::from_color(color) }.to_color(),
+            _ => return bad_args("a color"),
+        },
+        _ => return Err((ExprErrorKind::UnknownFunction, format!("Unknown function {}()", name))),
+    };
+    Ok(ExprValue::Color(color))
+}
+
+impl ThemeDocument {
+    /// Set the expression of a color field and evaluate all expressions again. The expression is
+    /// not stored if it cannot be parsed or would create a cycle.
+    pub fn set_expression(&mut self, field: &str, text: &str) -> Result<(), Vec<ExprError>> {
+        if let Err((kind, message)) = parse_expr(text) {
+            return Err(vec![ExprError::new(field, kind, message)]);
+        }
+        if self.colors.get(field).is_none() {
+            return Err(vec![ExprError::new(field, ExprErrorKind::UnknownReference, "Not a color field".to_string())]);
+        }
+        let previous = self.expressions.insert(field.to_string(), text.to_string());
+        let result = self.apply_expressions();
+        let cycle = match &result {
+            Err(errors) => errors.iter().any(|e| e.kind == ExprErrorKind::Cycle),
+            Ok(()) => false,
+        };
+        if cycle {
+            match previous {
+                Some(previous) => self.expressions.insert(field.to_string(), previous),
+                None => self.expressions.remove(field),
+            };
+        } else if self.extends.is_some() {
+            self.overrides.insert(format!("{}{}", EXPRESSION_FIELD_PREFIX, field));
+            self.overrides.remove(&format!("{}{}", COLOR_FIELD_PREFIX, field));
+        }
+        result
+    }
+
+    /// Remove the expression of a color field. The field keeps its last evaluated color.
+    pub fn clear_expression(&mut self, field: &str) -> bool {
+        let removed = self.expressions.remove(field).is_some();
+        self.overrides.remove(&format!("{}{}", EXPRESSION_FIELD_PREFIX, field));
+        if removed && self.extends.is_some() {
+            self.overrides.insert(format!("{}{}", COLOR_FIELD_PREFIX, field));
+        }
+        removed
+    }
+
+    /// Evaluate all expressions in dependency order and store the results in colors. Every problem
+    /// is reported, and fields whose expressions fail keep their current color.
+    pub fn apply_expressions(&mut self) -> Result<(), Vec<ExprError>> {
+        let mut errors: Vec<ExprError> = Vec::new();
+        let mut parsed: BTreeMap<String, Expr> = BTreeMap::new();
+        for (field, text) in &self.expressions {
+            if self.colors.get(field).is_none() {
+                errors.push(ExprError::new(field, ExprErrorKind::UnknownReference, "Not a color field".to_string()));
+                continue;
+            }
+            match parse_expr(text) {
+                Ok(expr) => {
+                    parsed.insert(field.clone(), expr);
+                }
+                Err((kind, message)) => errors.push(ExprError::new(field, kind, message)),
+            }
+        }
+
+        let (order, cycle_errors) = expression_order(&parsed);
+        errors.extend(cycle_errors);
+        for field in order {
+            match parsed[&field].eval(&self.colors) {
+                Ok(ExprValue::Color(color)) => {
+                    self.colors.set(&field, color);
+                }
+                Ok(ExprValue::Number(_)) => {
+                    let message = "Expression must be a color, not a number".to_string();
+                    errors.push(ExprError::new(&field, ExprErrorKind::BadArguments, message));
+                }
+                Err((kind, message)) => errors.push(ExprError::new(&field, kind, message)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Sort the expressions so that every field comes after the expression fields it depends on.
+/// Fields that are part of a cycle, or depend on one, are left out and reported as errors. Every field
+/// of a cycle gets its own error, with the whole cycle in the message.
+pub fn expression_order(parsed: &BTreeMap<String, Expr>) -> (Vec<String>, Vec<ExprError>) {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Visiting,
+        Done,
+        Failed,
+    }
+
+    fn visit(
+        field: &str,
+        parsed: &BTreeMap<String, Expr>,
+        marks: &mut BTreeMap<String, Mark>,
+        stack: &mut Vec<String>,
+        order: &mut Vec<String>,
+        errors: &mut Vec<ExprError>,
+    ) -> bool {
+        match marks.get(field) {
+            Some(Mark::Done) => return true,
+            Some(Mark::Failed) => return false,
+            Some(Mark::Visiting) => {
+                let start = stack.iter().position(|f| f == field).unwrap_or(0);
+                let mut cycle: Vec<String> = stack[start..].to_vec();
+                cycle.push(field.to_string());
+                let message = format!("Expression cycle: {}", cycle.join(" -> "));
+                for member in &stack[start..] {
+                    if !errors.iter().any(|e| &e.field == member) {
+                        errors.push(ExprError::new(member, ExprErrorKind::Cycle, message.clone()));
+                    }
+                }
+                return false;
+            }
+            None => {}
+        }
+        marks.insert(field.to_string(), Mark::Visiting);
+        stack.push(field.to_string());
+        let mut failed: Option<String> = None;
+        for name in parsed[field].references() {
+            if parsed.contains_key(&name) && !visit(&name, parsed, marks, stack, order, errors) {
+                failed = failed.or(Some(name));
+            }
+        }
+        stack.pop();
+        match failed {
+            None => {
+                marks.insert(field.to_string(), Mark::Done);
+                order.push(field.to_string());
+                true
+            }
+            Some(name) => {
+                // Fields in a cycle were reported when it was found, and the others depend on one
+                if !errors.iter().any(|e| e.field == field) {
+                    let message = format!("Depends on {}, which is part of an expression cycle", name);
+                    errors.push(ExprError::new(field, ExprErrorKind::Cycle, message));
+                }
+                marks.insert(field.to_string(), Mark::Failed);
+                false
+            }
+        }
+    }
+
+    let mut marks: BTreeMap<String, Mark> = BTreeMap::new();
+    let mut order: Vec<String> = Vec::new();
+    let mut errors: Vec<ExprError> = Vec::new();
+    for field in parsed.keys() {
+        visit(field, parsed, &mut marks, &mut Vec::new(), &mut order, &mut errors);
+    }
+    (order, errors)
+}
+
+struct ExprParser {
+    chars: Vec<char>,
+    index: usize,
+    /// The nesting of the function call being parsed
+    depth: usize,
+}
+
+impl ExprParser {
+    fn syntax_error(&self, message: &str) -> (ExprErrorKind, String) {
+        (ExprErrorKind::Syntax, format!("{} at column {}", message, self.index + 1))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, |c| c.is_whitespace()) {
+            self.index += 1;
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, test: F) -> String {
+        let start = self.index;
+        while self.peek().map_or(false, |c| test(c)) {
+            self.index += 1;
+        }
+        self.chars[start..self.index].iter().collect()
+    }
+
+    fn parse(&mut self) -> Result<Expr, (ExprErrorKind, String)> {
+        match self.peek() {
+            Some('#') => {
+                self.index += 1;
+                let hex = self.take_while(|c| c.is_ascii_hexdigit());
+                match parse_hex_color(&hex) {
+                    Some(color) => Ok(Expr::Color(color)),
+                    None => Err(self.syntax_error(&format!("Invalid hex color #{}", hex))),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' || c == '-' => {
+                let text = self.take_while(|c| c.is_ascii_digit() || c == '.' || c == '-');
+                let number: f32 = match text.parse() {
+                    Ok(number) => number,
+                    Err(_) => return Err(self.syntax_error(&format!("Invalid number {}", text))),
+                };
+                if self.peek() == Some('%') {
+                    self.index += 1;
+                    Ok(Expr::Number(number / 100.0))
+                } else {
+                    Ok(Expr::Number(number))
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                self.skip_whitespace();
+                if self.peek() != Some('(') {
+                    return match color_field_name(&name) {
+                        Some(field) => Ok(Expr::Ref(field.to_string())),
+                        None => Err((ExprErrorKind::UnknownReference, format!("Unknown color {:?}", name))),
+                    };
+                }
+                let name = name.to_lowercase();
+                if !EXPR_FUNCTIONS.contains(&name.as_str()) {
+                    return Err((ExprErrorKind::UnknownFunction, format!("Unknown function {}()", name)));
+                }
+                if self.depth == MAX_EXPR_DEPTH {
+                    return Err(self.syntax_error("Expression is nested too deeply"));
+                }
+                self.index += 1;
+                self.depth += 1;
+                let mut args: Vec<Expr> = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some(')') && args.is_empty() {
+                        self.index += 1;
+                        break;
+                    }
+                    args.push(self.parse()?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.index += 1,
+                        Some(')') => {
+                            self.index += 1;
+                            break;
+                        }
+                        _ => return Err(self.syntax_error("Expected ',' or ')'")),
+                    }
+                }
+                self.depth -= 1;
+                Ok(Expr::Call(name, args))
+            }
+            Some(c) => Err(self.syntax_error(&format!("Unexpected character '{}'", c))),
+            None => Err(self.syntax_error("Unexpected end of expression")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(expressions: &[(&str, &str)]) -> BTreeMap<String, Expr> {
+        expressions.iter().map(|(field, text)| (field.to_string(), parse_expr(text).unwrap())).collect()
+    }
+
+    fn eval(text: &str) -> Color {
+        let document = builtin_themes().remove(0);
+        match parse_expr(text).unwrap().eval(&document.colors) {
+            Ok(ExprValue::Color(color)) => color,
+            other => panic!("{:?} evaluated to {:?}", text, other),
+        }
+    }
+
+    fn assert_color(actual: Color, expected: Color) {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.002;
+        assert!(
+            close(actual.r, expected.r) && close(actual.g, expected.g)
+                && close(actual.b, expected.b) && close(actual.a, expected.a),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn parse_calls_and_references() {
+        let expr = parse_expr("darken(fg_color, 10%)").unwrap();
+        assert_eq!(expr, Expr::Call("darken".to_string(), vec![Expr::Ref("fg_color".to_string()), Expr::Number(0.1)]));
+        assert_eq!(parse_expr(" bg ").unwrap(), Expr::Ref("bg_color".to_string()));
+        assert_eq!(parse_expr("#FF0000").unwrap(), Expr::Color(Color::RED));
+        let expr = parse_expr("MIX(bg, #000, 0.25)").unwrap();
+        assert_eq!(expr.references(), vec!["bg_color".to_string()]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let kind = |text: &str| parse_expr(text).unwrap_err().0;
+        assert_eq!(kind("foo(bg)"), ExprErrorKind::UnknownFunction);
+        assert_eq!(kind("nope"), ExprErrorKind::UnknownReference);
+        assert_eq!(kind("mix(bg,"), ExprErrorKind::Syntax);
+        assert_eq!(kind("bg bg"), ExprErrorKind::Syntax);
+        assert_eq!(kind("#12"), ExprErrorKind::Syntax);
+        assert_eq!(kind(""), ExprErrorKind::Syntax);
+    }
+
+    #[test]
+    fn parse_depth_limit() {
+        let nested = |depth: usize| format!("{}bg{}", "invert(".repeat(depth), ")".repeat(depth));
+        assert!(parse_expr(&nested(MAX_EXPR_DEPTH)).is_ok());
+        let (kind, message) = parse_expr(&nested(MAX_EXPR_DEPTH + 1)).unwrap_err();
+        assert_eq!(kind, ExprErrorKind::Syntax);
+        assert!(message.contains("nested too deeply"));
+        assert!(parse_expr(&nested(100_000)).is_err());
+    }
+
+    #[test]
+    fn evaluate_functions() {
+        assert_color(eval("mix(#000000, #FFFFFF)"), Color { r: 0.5, g: 0.5, b: 0.5, a: 1.0 });
+        assert_color(eval("mix(#000000, #FFFFFF, 25%)"), Color { r: 0.25, g: 0.25, b: 0.25, a: 1.0 });
+        assert_color(eval("invert(#FFFFFF)"), Color::BLACK);
+        assert_color(eval("alpha(#FF0000, 0.5)"), Color { a: 0.5, ..Color::RED });
+        assert_color(eval("grayscale(#FF0000)"), Color { r: 0.5, g: 0.5, b: 0.5, a: 1.0 });
+        assert_color(eval("darken(#FF0000, 20%)"), Color { r: 0.6, g: 0.0, b: 0.0, a: 1.0 });
+        assert_color(eval("lighten(#FF0000, 20%)"), Color { r: 1.0, g: 0.4, b: 0.4, a: 1.0 });
+        assert_color(eval("desaturate(#FF0000, 100%)"), Color { r: 0.5, g: 0.5, b: 0.5, a: 1.0 });
+    }
+
+    #[test]
+    fn evaluate_errors() {
+        let colors = builtin_themes().remove(0).colors;
+        let error = |text: &str| parse_expr(text).unwrap().eval(&colors).unwrap_err().0;
+        assert_eq!(error("darken(bg)"), ExprErrorKind::BadArguments);
+        assert_eq!(error("mix(bg, 0.5)"), ExprErrorKind::BadArguments);
+        assert_eq!(error("invert(0.5)"), ExprErrorKind::BadArguments);
+    }
+
+    #[test]
+    fn apply_in_dependency_order() {
+        let mut document = builtin_themes().remove(0);
+        document.colors.bg_color = Color::WHITE;
+        document.expressions.insert("cursor_color".to_string(), "border".to_string());
+        document.expressions.insert("border_color".to_string(), "invert(bg)".to_string());
+        assert_eq!(document.apply_expressions(), Ok(()));
+        assert_color(document.colors.border_color, Color::BLACK);
+        assert_color(document.colors.cursor_color, Color::BLACK);
+    }
+
+    #[test]
+    fn every_field_of_a_cycle_is_reported() {
+        let parsed = parsed(&[
+            ("border_color", "darken(fg, 10%)"),
+            ("fg_color", "mix(border, bg)"),
+            ("cursor_color", "fg"),
+            ("bg_pressed_color", "darken(bg, 5%)"),
+        ]);
+        let (order, errors) = expression_order(&parsed);
+        assert_eq!(order, vec!["bg_pressed_color".to_string()]);
+        let mut fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        fields.sort();
+        assert_eq!(fields, vec!["border_color", "cursor_color", "fg_color"]);
+        assert!(errors.iter().all(|e| e.kind == ExprErrorKind::Cycle));
+    }
+
+    #[test]
+    fn self_reference_is_a_cycle() {
+        let (order, errors) = expression_order(&parsed(&[("bg_color", "lighten(bg, 10%)")]));
+        assert!(order.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "bg_color");
+    }
+
+    #[test]
+    fn set_expression_rejects_cycles() {
+        let mut document = builtin_themes().remove(0);
+        assert!(document.set_expression("border_color", "darken(fg, 10%)").is_ok());
+        let errors = document.set_expression("fg_color", "lighten(border, 10%)").unwrap_err();
+        assert!(errors.iter().any(|e| e.kind == ExprErrorKind::Cycle));
+        assert!(!document.expressions.contains_key("fg_color"));
+        assert!(document.apply_expressions().is_ok());
+    }
+}
@@ -0,0 +1,184 @@
+/// Generic access to theme values by field path, such as "font_size" or "colors.bg_color". The paths
+/// match the keys of the theme file, so tools that work across all fields (inheritance, inspectors,
+/// diffs) do not need to know the document structure.
+///
+use super::*;
+
+use std::fmt;
+
+use quicksilver::graphics::Color;
+use serde_json::Value;
+
+/// Every field path of a theme document, in file order
+pub const THEME_FIELDS: [&str; 13] = [
+    "font_size",
+    "fonts.default",
+    "fonts.title",
+    "colors.bg_color",
+    "colors.fg_color",
+    "colors.border_color",
+    "colors.button_bg_color",
+    "colors.button_fg_color",
+    "colors.bg_pressed_color",
+    "colors.fg_highlight_color",
+    "colors.cursor_color",
+    "border.style",
+    "border.width",
+];
+
+/// The prefix of color field paths
+pub const COLOR_FIELD_PREFIX: &str = "colors.";
+/// The path of the theme name. It can be read and set like the THEME_FIELDS, but is not one of them,
+/// since a derived theme always has its own name rather than inheriting it.
+pub const NAME_FIELD: &str = "name";
+
+/// The categories of theme fields, as grouped in the property inspector
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldGroup {
+    Colors,
+    Typography,
+    Borders,
+}
+
+impl FieldGroup {
+    pub const ALL: [FieldGroup; 3] = [FieldGroup::Colors, FieldGroup::Typography, FieldGroup::Borders];
+
+    /// The group of a field path
+    pub fn of(path: &str) -> FieldGroup {
+        if path.starts_with(COLOR_FIELD_PREFIX) {
+            FieldGroup::Colors
+        } else if path.starts_with("border.") {
+            FieldGroup::Borders
+        } else {
+            FieldGroup::Typography
+        }
+    }
+
+    /// The field paths in this group, in file order
+    pub fn fields(&self) -> Vec<&'static str> {
+        THEME_FIELDS.iter().cloned().filter(|path| FieldGroup::of(path) == *self).collect()
+    }
+}
+
+impl fmt::Display for FieldGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldGroup::Colors => write!(f, "Colors"),
+            FieldGroup::Typography => write!(f, "Typography"),
+            FieldGroup::Borders => write!(f, "Borders"),
+        }
+    }
+}
+
+/// The value of a single theme field
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Color(Color),
+    Number(f32),
+    Text(String),
+    Border(BorderKind),
+}
+
+impl FieldValue {
+    /// The value as it is written in a theme file
+    pub fn to_json(&self) -> Value {
+        match self {
+            FieldValue::Color(color) => Value::String(hex_string(color)),
+            FieldValue::Number(n) => serde_json::Number::from_f64(*n as f64).map_or(Value::Null, Value::Number),
+            FieldValue::Text(text) => Value::String(text.clone()),
+            FieldValue::Border(BorderKind::None) => Value::String("none".to_string()),
+            FieldValue::Border(BorderKind::Solid) => Value::String("solid".to_string()),
+        }
+    }
+
+    /// Read the value of a field from a theme file. The path decides the expected type.
+    pub fn from_json(path: &str, value: &Value) -> Option<FieldValue> {
+        if path.starts_with(COLOR_FIELD_PREFIX) {
+            return value.as_str().and_then(parse_hex_color).map(FieldValue::Color);
+        }
+        match path {
+            "font_size" | "border.width" => value.as_f64().map(|n| FieldValue::Number(n as f32)),
+            "fonts.default" | "fonts.title" => value.as_str().map(|s| FieldValue::Text(s.to_string())),
+            "border.style" => match value.as_str() {
+                Some("none") => Some(FieldValue::Border(BorderKind::None)),
+                Some("solid") => Some(FieldValue::Border(BorderKind::Solid)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldValue::Color(color) => write!(f, "{}", hex_string(color)),
+            FieldValue::Number(n) => write!(f, "{}", n),
+            FieldValue::Text(text) => write!(f, "{}", text),
+            FieldValue::Border(BorderKind::None) => write!(f, "none"),
+            FieldValue::Border(BorderKind::Solid) => write!(f, "solid"),
+        }
+    }
+}
+
+impl ThemeDocument {
+    /// Get the value of a field by path
+    pub fn field(&self, path: &str) -> Option<FieldValue> {
+        if path.starts_with(COLOR_FIELD_PREFIX) {
+            return self.colors.get(&path[COLOR_FIELD_PREFIX.len()..]).map(FieldValue::Color);
+        }
+        let value = match path {
+            NAME_FIELD => FieldValue::Text(self.name.clone()),
+            "font_size" => FieldValue::Number(self.font_size),
+            "fonts.default" => FieldValue::Text(self.fonts.default.clone()),
+            "fonts.title" => FieldValue::Text(self.fonts.title.clone()),
+            "border.style" => FieldValue::Border(self.border.style),
+            "border.width" => FieldValue::Number(self.border.width),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Set the value of a field by path. Returns true if the value changed, and false if there is no such
+    /// field, the value has the wrong type for it or the field already has the value.
+    pub fn set_field(&mut self, path: &str, value: FieldValue) -> bool {
+        if self.field(path).as_ref() == Some(&value) {
+            return false;
+        }
+        match (path, value) {
+            (_, FieldValue::Color(color)) if path.starts_with(COLOR_FIELD_PREFIX) => {
+                self.colors.set(&path[COLOR_FIELD_PREFIX.len()..], color)
+            }
+            (NAME_FIELD, FieldValue::Text(text)) => {
+                self.name = text;
+                true
+            }
+            ("font_size", FieldValue::Number(n)) => {
+                self.font_size = n;
+                true
+            }
+            ("fonts.default", FieldValue::Text(text)) => {
+                self.fonts.default = text;
+                true
+            }
+            ("fonts.title", FieldValue::Text(text)) => {
+                self.fonts.title = text;
+                true
+            }
+            ("border.style", FieldValue::Border(style)) => {
+                self.border.style = style;
+                true
+            }
+            ("border.width", FieldValue::Number(n)) => {
+                self.border.width = n;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The paths of the fields whose values differ from another document, in file order
+    pub fn differing_fields(&self, other: &ThemeDocument) -> Vec<&'static str> {
+        THEME_FIELDS.iter().cloned().filter(|path| self.field(path) != other.field(path)).collect()
+    }
+}
@@ -0,0 +1,57 @@
+/// Hex string conversions for Quicksilver colors. Theme files store every color as a hex string
+/// in the form #RRGGBB, or #RRGGBBAA when the color is not fully opaque.
+///
+use quicksilver::graphics::Color;
+
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+/// Parse a hex color string. Accepts #RGB, #RGBA, #RRGGBB and #RRGGBBAA with or without the leading #.
+/// Unlike Color::from_hex, this returns None instead of panicking on malformed input.
+pub fn parse_hex_color(value: &str) -> Option<Color> {
+    let hex = value.trim().trim_start_matches('#');
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let (r, g, b, a) = match hex.len() {
+        3 | 4 => {
+            let short = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|v| v * 17);
+            let alpha = if hex.len() == 4 { short(3)? } else { 255 };
+            (short(0)?, short(1)?, short(2)?, alpha)
+        }
+        6 => (channel(0)?, channel(2)?, channel(4)?, 255),
+        8 => (channel(0)?, channel(2)?, channel(4)?, channel(6)?),
+        _ => return None,
+    };
+    Some(Color { r: r as f32 / 255.0, g: g as f32 / 255.0, b: b as f32 / 255.0, a: a as f32 / 255.0 })
+}
+
+/// Format a color as an uppercase hex string. The alpha channel is only written if it is not 1.0
+pub fn hex_string(color: &Color) -> String {
+    let byte = |v: f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+    if byte(color.a) == 255 {
+        format!("#{:02X}{:02X}{:02X}", byte(color.r), byte(color.g), byte(color.b))
+    } else {
+        format!("#{:02X}{:02X}{:02X}{:02X}", byte(color.r), byte(color.g), byte(color.b), byte(color.a))
+    }
+}
+
+/// Serde adapter for Color fields. Use with #[serde(with = "hex_color")]
+pub mod hex_color {
+    use super::*;
+
+    pub fn serialize<S>(color: &Color, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&hex_string(color))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Color, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        parse_hex_color(&value).ok_or_else(|| de::Error::custom(format!("invalid hex color: {:?}", value)))
+    }
+}
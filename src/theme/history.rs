@@ -0,0 +1,457 @@
+/// Undo and redo of theme edits. Every change to the list of themes is a ThemeCommand that holds what
+/// it needs to be applied again and reverted, and is recorded in a ThemeHistory:
+///
+/// let command = ThemeCommand::Add { index: themes.len(), document };
+/// command.apply(&mut themes)?;
+/// history.record(command, frame);
+/// ...
+/// if let Some(command) = history.undo() { command.revert(&mut themes)?; }
+///
+/// Edits of the same field recorded within COALESCE_FRAMES of each other are merged into one command,
+/// so a run of color picker clicks or stepper clicks is undone in one step. Frames are counted by the
+/// caller, since std::time::Instant is not available on wasm.
+///
+use super::*;
+
+use std::collections::{BTreeSet, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+
+/// The most commands kept for undo. Each edit holds two copies of a theme, a few KB, so the history
+/// stays under a MB.
+pub const HISTORY_LIMIT: usize = 100;
+/// Edits of the same field less than this many frames apart are coalesced. About half a second at 60 FPS.
+pub const COALESCE_FRAMES: u64 = 30;
+
+/// A reversible change to the list of themes
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThemeCommand {
+    /// Change the values of a theme, matched by id. The key is the path of the one field that changed,
+    /// if only one did, and edits with the same key can be coalesced. See ThemeCommand::edit.
+    Edit { before: ThemeDocument, after: ThemeDocument, key: Option<String> },
+    /// Add a theme at a position in the list
+    Add { index: usize, document: ThemeDocument },
+    /// Remove a theme from a position in the list, and the saved file of the theme if there is one
+    Remove { index: usize, document: ThemeDocument, file: Option<PathBuf> },
+}
+
+impl ThemeCommand {
+    /// An edit of a theme, keyed by the field that changed. Besides the THEME_FIELDS paths, the key can be
+    /// "name", an expression such as "expressions.border_color" or a state color such as
+    /// "states.hover.bg_color". Colors evaluated from expressions are not counted, so a change that is
+    /// carried to derived colors still has a key.
+    pub fn edit(before: ThemeDocument, after: ThemeDocument) -> Self {
+        let key = edit_key(&before, &after);
+        ThemeCommand::Edit { before, after, key }
+    }
+
+    /// The id of the theme the command changes
+    pub fn theme_id(&self) -> u32 {
+        match self {
+            ThemeCommand::Edit { after, .. } => after.id,
+            ThemeCommand::Add { document, .. } => document.id,
+            ThemeCommand::Remove { document, .. } => document.id,
+        }
+    }
+
+    /// The position in the list of a theme that is added or removed
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            ThemeCommand::Edit { .. } => None,
+            ThemeCommand::Add { index, .. } | ThemeCommand::Remove { index, .. } => Some(*index),
+        }
+    }
+
+    /// A short description for menus. Eg: "Delete My theme"
+    pub fn title(&self) -> String {
+        match self {
+            ThemeCommand::Edit { after, key: Some(key), .. } => format!("Change {} of {}", key, after.name),
+            ThemeCommand::Edit { after, key: None, .. } => format!("Edit {}", after.name),
+            ThemeCommand::Add { document, .. } => format!("Add {}", document.name),
+            ThemeCommand::Remove { document, .. } => format!("Delete {}", document.name),
+        }
+    }
+
+    /// Make the change. The list is always changed, even if a file could not be deleted.
+    pub fn apply(&self, themes: &mut Vec<ThemeDocument>) -> Result<(), ThemeError> {
+        match self {
+            ThemeCommand::Edit { after, .. } => replace_theme(themes, after),
+            ThemeCommand::Add { index, document } => insert_theme(themes, *index, document),
+            ThemeCommand::Remove { document, file, .. } => {
+                themes.retain(|theme| theme.id != document.id);
+                if let Some(path) = file {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Undo the change. A removed theme is saved to its file again.
+    pub fn revert(&self, themes: &mut Vec<ThemeDocument>) -> Result<(), ThemeError> {
+        match self {
+            ThemeCommand::Edit { before, .. } => replace_theme(themes, before),
+            ThemeCommand::Add { document, .. } => themes.retain(|theme| theme.id != document.id),
+            ThemeCommand::Remove { index, document, file } => {
+                insert_theme(themes, *index, document);
+                if let Some(path) = file {
+                    document.save(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge a later edit of the same field of the same theme into this one. Returns false if they cannot
+    /// be merged.
+    fn coalesce(&mut self, next: &ThemeCommand) -> bool {
+        match (self, next) {
+            (
+                ThemeCommand::Edit { after, key: Some(key), .. },
+                ThemeCommand::Edit { after: next_after, key: Some(next_key), .. },
+            ) if key == next_key && after.id == next_after.id => {
+                *after = next_after.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// True for an edit that was coalesced back to where it started
+    fn is_noop(&self) -> bool {
+        match self {
+            ThemeCommand::Edit { before, after, .. } => before == after,
+            _ => false,
+        }
+    }
+}
+
+/// The undo and redo stacks of theme commands
+#[derive(Debug, Clone)]
+pub struct ThemeHistory {
+    undo: VecDeque<ThemeCommand>,
+    redo: Vec<ThemeCommand>,
+    /// The frame the last command was recorded in, or None if it cannot be coalesced with
+    last_frame: Option<u64>,
+    limit: usize,
+}
+
+impl Default for ThemeHistory {
+    fn default() -> Self {
+        ThemeHistory { undo: VecDeque::new(), redo: Vec::new(), last_frame: None, limit: HISTORY_LIMIT }
+    }
+}
+
+impl ThemeHistory {
+    pub fn new() -> Self {
+        ThemeHistory::default()
+    }
+
+    /// Builder method to keep a different number of commands
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(1);
+        self
+    }
+
+    /// Record a command that was applied in the given frame. This clears the redo stack. The oldest
+    /// command is dropped when there are more than the limit.
+    pub fn record(&mut self, command: ThemeCommand, frame: u64) {
+        self.redo.clear();
+        let recent = self.last_frame.map_or(false, |last| frame.saturating_sub(last) <= COALESCE_FRAMES);
+        self.last_frame = Some(frame);
+        if recent {
+            if let Some(last) = self.undo.back_mut() {
+                if last.coalesce(&command) {
+                    if last.is_noop() {
+                        self.undo.pop_back();
+                        self.last_frame = None;
+                    }
+                    return;
+                }
+            }
+        }
+        self.undo.push_back(command);
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Take the last command to revert. It moves to the redo stack.
+    pub fn undo(&mut self) -> Option<ThemeCommand> {
+        let command = self.undo.pop_back()?;
+        self.redo.push(command.clone());
+        self.last_frame = None;
+        Some(command)
+    }
+
+    /// Take the last undone command to apply again. It moves back to the undo stack.
+    pub fn redo(&mut self) -> Option<ThemeCommand> {
+        let command = self.redo.pop()?;
+        self.undo.push_back(command.clone());
+        self.last_frame = None;
+        Some(command)
+    }
+
+    /// The command that undo would revert
+    pub fn next_undo(&self) -> Option<&ThemeCommand> {
+        self.undo.back()
+    }
+
+    /// The command that redo would apply
+    pub fn next_redo(&self) -> Option<&ThemeCommand> {
+        self.redo.last()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.last_frame = None;
+    }
+}
+
+/// The path of the one field that differs between two versions of a theme, or None if more than one does
+fn edit_key(before: &ThemeDocument, after: &ThemeDocument) -> Option<String> {
+    let is_evaluated = |path: &str| {
+        path.starts_with(COLOR_FIELD_PREFIX) && after.expressions.contains_key(&path[COLOR_FIELD_PREFIX.len()..])
+    };
+    let mut paths: Vec<String> =
+        before.differing_fields(after).into_iter().filter(|path| !is_evaluated(path)).map(String::from).collect();
+    if before.name != after.name {
+        paths.push(NAME_FIELD.to_string());
+    }
+    let fields: BTreeSet<&String> = before.expressions.keys().chain(after.expressions.keys()).collect();
+    for field in fields {
+        if before.expressions.get(field) != after.expressions.get(field) {
+            paths.push(format!("{}{}", EXPRESSION_FIELD_PREFIX, field));
+        }
+    }
+    for state in ControlState::ALL.iter() {
+        for field in StateColors::FIELDS.iter() {
+            let color = |document: &ThemeDocument| document.states.get(*state).and_then(|colors| colors.get(field));
+            if color(before) != color(after) {
+                paths.push(format!("{}.{}.{}", STATES_FIELD, state.key(), field));
+            }
+        }
+    }
+    if paths.len() == 1 {
+        paths.pop()
+    } else {
+        None
+    }
+}
+
+fn replace_theme(themes: &mut Vec<ThemeDocument>, document: &ThemeDocument) {
+    if let Some(theme) = themes.iter_mut().find(|theme| theme.id == document.id) {
+        *theme = document.clone();
+    }
+}
+
+fn insert_theme(themes: &mut Vec<ThemeDocument>, index: usize, document: &ThemeDocument) {
+    if !themes.iter().any(|theme| theme.id == document.id) {
+        themes.insert(index.min(themes.len()), document.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use quicksilver::graphics::Color;
+
+    fn theme() -> ThemeDocument {
+        builtin_themes().remove(0)
+    }
+
+    /// An edit of the font size of the theme from one size to another
+    fn font_size_edit(from: f32, to: f32) -> ThemeCommand {
+        let before = ThemeDocument { font_size: from, ..theme() };
+        let after = ThemeDocument { font_size: to, ..theme() };
+        ThemeCommand::edit(before, after)
+    }
+
+    #[test]
+    fn edit_keys() {
+        let before = theme();
+        let key = |after: &ThemeDocument| ThemeCommand::edit(before.clone(), after.clone()).title();
+        let name = format!("of {}", before.name);
+
+        let mut after = before.clone();
+        after.colors.bg_color = Color::RED;
+        assert_eq!(key(&after), format!("Change colors.bg_color {}", name));
+
+        let mut after = before.clone();
+        after.name = "Renamed".to_string();
+        assert_eq!(key(&after), "Change name of Renamed");
+
+        let mut after = before.clone();
+        after.set_expression("border_color", "invert(bg)").unwrap();
+        assert_eq!(key(&after), format!("Change expressions.border_color {}", name));
+
+        let mut after = before.clone();
+        after.states.hover.set("bg_color", Some(Color { r: 0.1, g: 0.2, b: 0.3, a: 1.0 }));
+        assert_eq!(key(&after), format!("Change states.hover.bg_color {}", name));
+
+        let mut after = before.clone();
+        after.colors.bg_color = Color::RED;
+        after.font_size += 1.0;
+        assert_eq!(key(&after), format!("Edit {}", before.name));
+    }
+
+    #[test]
+    fn evaluated_colors_do_not_count() {
+        let mut before = theme();
+        before.set_expression("border_color", "invert(bg)").unwrap();
+        let mut after = before.clone();
+        after.colors.bg_color = Color::WHITE;
+        after.apply_expressions().unwrap();
+        assert_ne!(before.colors.border_color, after.colors.border_color);
+        match ThemeCommand::edit(before, after) {
+            ThemeCommand::Edit { key, .. } => assert_eq!(key, Some("colors.bg_color".to_string())),
+            command => panic!("{:?}", command),
+        }
+    }
+
+    #[test]
+    fn rename_and_undo() {
+        let mut themes = builtin_themes();
+        let mut history = ThemeHistory::new();
+        let original = themes[0].clone();
+        let mut document = original.clone();
+        for (frame, name) in ["D", "Du", "Dusk"].iter().enumerate() {
+            let before = document.clone();
+            assert!(document.override_field(NAME_FIELD, FieldValue::Text(name.to_string())));
+            let command = ThemeCommand::edit(before, document.clone());
+            command.apply(&mut themes).unwrap();
+            history.record(command, frame as u64);
+        }
+        assert_eq!(themes[0].name, "Dusk");
+        assert!(themes[0].overrides.is_empty());
+        assert_eq!(history.next_undo().map(|command| command.title()), Some("Change name of Dusk".to_string()));
+
+        // The renames were coalesced, so one undo restores the original name
+        let command = history.undo().unwrap();
+        command.revert(&mut themes).unwrap();
+        assert_eq!(themes[0], original);
+        assert!(history.next_undo().is_none());
+        history.redo().unwrap().apply(&mut themes).unwrap();
+        assert_eq!(themes[0].name, "Dusk");
+    }
+
+    #[test]
+    fn edits_of_the_same_field_are_coalesced() {
+        let mut history = ThemeHistory::new();
+        history.record(font_size_edit(14.0, 15.0), 0);
+        history.record(font_size_edit(15.0, 16.0), 10);
+        history.record(font_size_edit(16.0, 17.0), 10 + COALESCE_FRAMES);
+        match history.undo() {
+            Some(ThemeCommand::Edit { before, after, .. }) => {
+                assert_eq!(before.font_size, 14.0);
+                assert_eq!(after.font_size, 17.0);
+            }
+            command => panic!("{:?}", command),
+        }
+        assert!(history.next_undo().is_none());
+    }
+
+    #[test]
+    fn edits_are_not_coalesced_after_a_pause_or_undo() {
+        let mut history = ThemeHistory::new();
+        history.record(font_size_edit(14.0, 15.0), 0);
+        history.record(font_size_edit(15.0, 16.0), COALESCE_FRAMES + 1);
+        assert_eq!(history.undo(), Some(font_size_edit(15.0, 16.0)));
+        history.record(font_size_edit(15.0, 18.0), COALESCE_FRAMES + 2);
+        assert_eq!(history.undo(), Some(font_size_edit(15.0, 18.0)));
+        assert_eq!(history.undo(), Some(font_size_edit(14.0, 15.0)));
+    }
+
+    #[test]
+    fn edits_of_different_fields_are_not_coalesced() {
+        let mut history = ThemeHistory::new();
+        history.record(font_size_edit(14.0, 15.0), 0);
+        let before = theme();
+        let after = ThemeDocument { name: "Renamed".to_string(), ..theme() };
+        history.record(ThemeCommand::edit(before, after), 1);
+        assert!(history.undo().is_some());
+        assert_eq!(history.undo(), Some(font_size_edit(14.0, 15.0)));
+    }
+
+    #[test]
+    fn coalesced_noop_is_removed() {
+        let mut history = ThemeHistory::new();
+        history.record(font_size_edit(14.0, 15.0), 0);
+        history.record(font_size_edit(15.0, 14.0), 1);
+        assert!(history.next_undo().is_none());
+        // The next edit starts a new command instead of merging into the removed one
+        history.record(font_size_edit(14.0, 16.0), 2);
+        assert_eq!(history.undo(), Some(font_size_edit(14.0, 16.0)));
+    }
+
+    #[test]
+    fn record_clears_redo() {
+        let mut history = ThemeHistory::new();
+        history.record(font_size_edit(14.0, 15.0), 0);
+        history.undo();
+        assert!(history.next_redo().is_some());
+        history.record(font_size_edit(14.0, 20.0), 100);
+        assert!(history.next_redo().is_none());
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn undo_and_redo_move_commands() {
+        let mut history = ThemeHistory::new();
+        let add = ThemeCommand::Add { index: 2, document: theme() };
+        history.record(add.clone(), 0);
+        assert_eq!(history.undo(), Some(add.clone()));
+        assert!(history.undo().is_none());
+        assert_eq!(history.redo(), Some(add.clone()));
+        assert!(history.redo().is_none());
+        assert_eq!(history.next_undo(), Some(&add));
+    }
+
+    #[test]
+    fn oldest_commands_are_dropped() {
+        let mut history = ThemeHistory::new();
+        for i in 0..HISTORY_LIMIT + 5 {
+            let size = 10.0 + i as f32;
+            history.record(font_size_edit(size, size + 1.0), i as u64 * (COALESCE_FRAMES + 1));
+        }
+        let mut count = 0;
+        while let Some(command) = history.undo() {
+            count += 1;
+            if history.next_undo().is_none() {
+                assert_eq!(command, font_size_edit(15.0, 16.0));
+            }
+        }
+        assert_eq!(count, HISTORY_LIMIT);
+        let mut history = ThemeHistory::new().with_limit(2);
+        for i in 0..3 {
+            history.record(ThemeCommand::Add { index: i, document: theme() }, i as u64 * 100);
+        }
+        assert_eq!(history.undo().and_then(|command| command.index()), Some(2));
+        assert_eq!(history.undo().and_then(|command| command.index()), Some(1));
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn apply_and_revert() {
+        let mut themes = builtin_themes();
+        let count = themes.len();
+        let document = ThemeDocument { id: 500, ..theme() };
+        let add = ThemeCommand::Add { index: 1, document: document.clone() };
+        add.apply(&mut themes).unwrap();
+        assert_eq!(themes[1].id, 500);
+        let edit = ThemeCommand::edit(document.clone(), ThemeDocument { font_size: 30.0, ..document.clone() });
+        edit.apply(&mut themes).unwrap();
+        assert_eq!(themes[1].font_size, 30.0);
+        edit.revert(&mut themes).unwrap();
+        assert_eq!(themes[1], document);
+        let remove = ThemeCommand::Remove { index: 1, document: document.clone(), file: None };
+        remove.apply(&mut themes).unwrap();
+        assert_eq!(themes.len(), count);
+        remove.revert(&mut themes).unwrap();
+        assert_eq!(themes[1].id, 500);
+        add.revert(&mut themes).unwrap();
+        assert_eq!(themes.len(), count);
+    }
+}
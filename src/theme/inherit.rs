@@ -0,0 +1,412 @@
+/// Theme inheritance. A theme file can declare "extends" with the id or slug of another theme and only
+/// store the fields it overrides:
+///
+/// { "id": 101, "name": "Dusk Owl", "extends": "dark-theme", "colors": { "bg_color": "#101820" } }
+///
+/// The base theme is looked up among the themes being loaded and the themes already registered,
+/// and may itself extend another theme.
+///
+use super::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// A theme file which stores only the fields it overrides
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivedTheme {
+    pub id: u32,
+    pub name: String,
+    /// The id or slug of the base theme
+    pub extends: String,
+    /// The overridden values, keyed by field path
+    pub values: BTreeMap<String, FieldValue>,
+    /// The color expressions set in this theme, keyed by color field name
+    pub expressions: BTreeMap<String, String>,
+    /// The state colors, which replace those of the base theme as a whole if set
+    pub states: Option<ThemeStates>,
+}
+
+/// A theme file as loaded from disk, before inheritance is resolved
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThemeSource {
+    Complete(ThemeDocument),
+    Derived(DerivedTheme),
+}
+
+impl ThemeSource {
+    /// Parse and validate a theme file. The expressions of a complete theme are evaluated, while those of
+    /// a derived theme are evaluated once it is applied to its base theme.
+    pub fn from_json(json: &str) -> Result<Self, ThemeError> {
+        let errors = validate_theme_json(json);
+        if !errors.is_empty() {
+            return Err(ThemeError::Validation(errors));
+        }
+        let root: serde_json::Value = serde_json::from_str(json)?;
+        let extends = match root["extends"].as_str() {
+            Some(extends) => extends.to_string(),
+            None => {
+                let mut document: ThemeDocument = serde_json::from_value(root)?;
+                document.apply_expressions().map_err(ThemeError::Expression)?;
+                return Ok(ThemeSource::Complete(document));
+            }
+        };
+        let mut values: BTreeMap<String, FieldValue> = BTreeMap::new();
+        for path in THEME_FIELDS.iter() {
+            let value = path.split('.').fold(&root, |value, key| &value[key]);
+            if let Some(value) = FieldValue::from_json(path, value) {
+                values.insert(path.to_string(), value);
+            }
+        }
+        let expressions: BTreeMap<String, String> = match root["expressions"].as_object() {
+            Some(object) => object.iter().filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string()))).collect(),
+            None => BTreeMap::new(),
+        };
+        let states = match root.get(STATES_FIELD) {
+            Some(states) => Some(serde_json::from_value(states.clone())?),
+            None => None,
+        };
+        let derived = DerivedTheme {
+            id: root["id"].as_u64().unwrap_or_default() as u32,
+            name: root["name"].as_str().unwrap_or_default().to_string(),
+            extends,
+            values,
+            expressions,
+            states,
+        };
+        Ok(ThemeSource::Derived(derived))
+    }
+
+    /// Read a theme file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ThemeError> {
+        let json = fs::read_to_string(path)?;
+        ThemeSource::from_json(&json)
+    }
+
+    pub fn id(&self) -> u32 {
+        match self {
+            ThemeSource::Complete(document) => document.id,
+            ThemeSource::Derived(derived) => derived.id,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            ThemeSource::Complete(document) => &document.name,
+            ThemeSource::Derived(derived) => &derived.name,
+        }
+    }
+
+    /// True if this is the theme named by an extends reference
+    pub fn matches_ref(&self, reference: &str) -> bool {
+        match self {
+            ThemeSource::Complete(document) => document.matches_ref(reference),
+            ThemeSource::Derived(derived) => {
+                derived.id.to_string() == reference || slugify(&derived.name, derived.id) == reference
+            }
+        }
+    }
+}
+
+impl DerivedTheme {
+    /// Build the full theme by applying the overrides to the base theme. The expressions of the base
+    /// theme are inherited too, except for colors this theme sets directly, and are evaluated again so
+    /// that derived colors follow the overridden ones.
+    pub fn apply(&self, base: &ThemeDocument) -> Result<ThemeDocument, ThemeError> {
+        let mut document = base.clone();
+        document.id = self.id;
+        document.name = self.name.clone();
+        document.extends = Some(self.extends.clone());
+        document.overrides = BTreeSet::new();
+        for (path, value) in &self.values {
+            // A value equal to the base value is still an override, as long as it fits the field
+            document.set_field(path, value.clone());
+            if document.field(path).as_ref() == Some(value) {
+                document.overrides.insert(path.clone());
+                if path.starts_with(COLOR_FIELD_PREFIX) {
+                    document.expressions.remove(&path[COLOR_FIELD_PREFIX.len()..]);
+                }
+            }
+        }
+        for (field, text) in &self.expressions {
+            document.expressions.insert(field.clone(), text.clone());
+            document.overrides.insert(format!("{}{}", EXPRESSION_FIELD_PREFIX, field));
+        }
+        if let Some(states) = &self.states {
+            document.states = states.clone();
+            document.overrides.insert(STATES_FIELD.to_string());
+        }
+        document.apply_expressions().map_err(ThemeError::Expression)?;
+        Ok(document)
+    }
+}
+
+impl ThemeDocument {
+    /// True if an extends reference names this theme, either by id or by slug
+    pub fn matches_ref(&self, reference: &str) -> bool {
+        self.id.to_string() == reference || self.slug() == reference
+    }
+
+    /// True if the field is inherited from the base theme rather than set in this theme
+    pub fn is_inherited(&self, path: &str) -> bool {
+        self.extends.is_some() && !self.overrides.contains(path)
+    }
+
+    /// Copy all inherited fields and expressions from the base theme, eg: after the base theme was
+    /// edited, and evaluate the expressions again
+    pub fn rebase(&mut self, base: &ThemeDocument) -> Result<(), Vec<ExprError>> {
+        let inherited: Vec<&str> = THEME_FIELDS.iter().cloned().filter(|path| self.is_inherited(path)).collect();
+        for path in inherited {
+            if let Some(value) = base.field(path) {
+                self.set_field(path, value);
+            }
+        }
+        if self.is_inherited(STATES_FIELD) {
+            self.states = base.states.clone();
+        }
+        let overrides = &self.overrides;
+        let is_own = |field: &str| {
+            overrides.contains(&format!("{}{}", EXPRESSION_FIELD_PREFIX, field))
+                || overrides.contains(&format!("{}{}", COLOR_FIELD_PREFIX, field))
+        };
+        self.expressions.retain(|field, _| is_own(field));
+        for (field, text) in &base.expressions {
+            if !is_own(field) {
+                self.expressions.insert(field.clone(), text.clone());
+            }
+        }
+        self.apply_expressions()
+    }
+
+    /// Set a field and, for a derived theme, mark it as overridden. Setting a color replaces its
+    /// expression, if it had one. The name is never marked, since it is not inherited. Returns false if
+    /// the value did not change.
+    pub fn override_field(&mut self, path: &str, value: FieldValue) -> bool {
+        let changed = self.set_field(path, value);
+        if changed && path.starts_with(COLOR_FIELD_PREFIX) {
+            let field = &path[COLOR_FIELD_PREFIX.len()..];
+            self.expressions.remove(field);
+            self.overrides.remove(&format!("{}{}", EXPRESSION_FIELD_PREFIX, field));
+        }
+        if changed && self.extends.is_some() && path != NAME_FIELD {
+            self.overrides.insert(path.to_string());
+        }
+        changed
+    }
+}
+
+/// Resolve the inheritance of a set of loaded theme files. Base themes are looked up first among the
+/// sources and then among the registered themes. Returns one result per source, in the same order.
+/// Themes whose base is missing or that are part of an inheritance cycle fail with an error.
+pub fn resolve_themes(
+    sources: &[ThemeSource],
+    registered: &[ThemeDocument],
+) -> Vec<Result<ThemeDocument, ThemeError>> {
+    let mut resolver = ThemeResolver { sources, registered, resolved: vec![None; sources.len()] };
+    (0..sources.len()).map(|index| resolver.resolve(index, &mut Vec::new())).collect()
+}
+
+struct ThemeResolver<'a> {
+    sources: &'a [ThemeSource],
+    registered: &'a [ThemeDocument],
+    /// Cache of sources that have been resolved, by index
+    resolved: Vec<Option<Result<ThemeDocument, String>>>,
+}
+
+impl<'a> ThemeResolver<'a> {
+    /// Resolve the source at index. The stack holds the indexes being resolved, to detect cycles.
+    fn resolve(&mut self, index: usize, stack: &mut Vec<usize>) -> Result<ThemeDocument, ThemeError> {
+        if let Some(result) = &self.resolved[index] {
+            return result.clone().map_err(ThemeError::Invalid);
+        }
+        if let Some(start) = stack.iter().position(|i| *i == index) {
+            let mut names: Vec<String> =
+                stack[start..].iter().map(|i| self.sources[*i].name().to_string()).collect();
+            names.push(self.sources[index].name().to_string());
+            return Err(ThemeError::InheritanceCycle(names));
+        }
+
+        // Copy the slice reference so that sources are not borrowed from self during recursion
+        let sources = self.sources;
+        let result = match &sources[index] {
+            ThemeSource::Complete(document) => Ok(document.clone()),
+            ThemeSource::Derived(derived) => {
+                stack.push(index);
+                let base = match sources.iter().position(|s| s.matches_ref(&derived.extends)) {
+                    Some(base_index) => self.resolve(base_index, stack),
+                    None => match self.registered.iter().find(|d| d.matches_ref(&derived.extends)) {
+                        Some(document) => Ok(document.clone()),
+                        None => Err(ThemeError::Invalid(format!("Unknown base theme {:?}", derived.extends))),
+                    },
+                };
+                stack.pop();
+                base.and_then(|base| derived.apply(&base))
+            }
+        };
+        // Cycle errors are not cached, so every theme in the cycle reports the full cycle
+        match &result {
+            Ok(document) => self.resolved[index] = Some(Ok(document.clone())),
+            Err(ThemeError::InheritanceCycle(_)) => {}
+            Err(ThemeError::Invalid(message)) => self.resolved[index] = Some(Err(message.clone())),
+            Err(err) => self.resolved[index] = Some(Err(err.to_string())),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use quicksilver::graphics::Color;
+
+    const DUSK: &str = r##"{"id": 101, "name": "Dusk", "extends": "dark-theme", "colors": {"bg_color": "#101820"}}"##;
+
+    fn dark() -> ThemeDocument {
+        builtin_themes().remove(1)
+    }
+
+    fn source(json: &str) -> ThemeSource {
+        ThemeSource::from_json(json).unwrap()
+    }
+
+    fn resolve_one(json: &str) -> ThemeDocument {
+        resolve_themes(&[source(json)], &builtin_themes()).remove(0).unwrap()
+    }
+
+    #[test]
+    fn complete_theme_evaluates_expressions() {
+        let mut document = dark();
+        document.colors.fg_color = Color::RED;
+        document.expressions.insert("fg_color".to_string(), "bg".to_string());
+        let json = serde_json::to_string(&document).unwrap();
+        match source(&json) {
+            ThemeSource::Complete(document) => assert_eq!(document.colors.fg_color, document.colors.bg_color),
+            ThemeSource::Derived(derived) => panic!("{} is not complete", derived.name),
+        }
+    }
+
+    #[test]
+    fn complete_theme_expression_error() {
+        let mut document = dark();
+        document.expressions.insert("fg_color".to_string(), "0.5".to_string());
+        let json = serde_json::to_string(&document).unwrap();
+        match ThemeSource::from_json(&json) {
+            Err(ThemeError::Expression(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].field, "fg_color");
+                assert_eq!(errors[0].kind, ExprErrorKind::BadArguments);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn derived_theme_from_json() {
+        let derived = match source(DUSK) {
+            ThemeSource::Derived(derived) => derived,
+            ThemeSource::Complete(document) => panic!("{} is not derived", document.name),
+        };
+        assert_eq!(derived.extends, "dark-theme");
+        let paths: Vec<&str> = derived.values.keys().map(|path| path.as_str()).collect();
+        assert_eq!(paths, vec!["colors.bg_color"]);
+        assert!(derived.states.is_none());
+        assert!(source(DUSK).matches_ref("dusk"));
+        assert!(source(DUSK).matches_ref("101"));
+    }
+
+    #[test]
+    fn resolve_applies_overrides() {
+        let document = resolve_one(DUSK);
+        let base = dark();
+        assert_eq!(document.colors.bg_color, parse_hex_color("#101820").unwrap());
+        assert_eq!(document.colors.fg_color, base.colors.fg_color);
+        assert_eq!(document.font_size, base.font_size);
+        assert_eq!(document.extends, Some("dark-theme".to_string()));
+        assert!(!document.is_inherited("colors.bg_color"));
+        assert!(document.is_inherited("colors.fg_color"));
+    }
+
+    #[test]
+    fn value_equal_to_base_is_an_override() {
+        let json = r#"{"id": 101, "name": "Dusk", "extends": "dark-theme", "font_size": SIZE}"#;
+        let document = resolve_one(&json.replace("SIZE", &dark().font_size.to_string()));
+        assert!(!document.is_inherited("font_size"));
+    }
+
+    #[test]
+    fn derived_expressions_follow_overrides() {
+        let json = r##"{"id": 101, "name": "Dusk", "extends": "dark-theme", "colors": {"bg_color": "#101820"},
+            "expressions": {"border_color": "bg"}}"##;
+        let document = resolve_one(json);
+        assert_eq!(document.colors.border_color, document.colors.bg_color);
+        assert!(!document.is_inherited("expressions.border_color"));
+    }
+
+    #[test]
+    fn resolve_chain_of_sources() {
+        let night = r#"{"id": 102, "name": "Night", "extends": "dusk", "font_size": 20}"#;
+        let results = resolve_themes(&[source(night), source(DUSK)], &builtin_themes());
+        let document = results[0].as_ref().unwrap();
+        assert_eq!(document.colors.bg_color, parse_hex_color("#101820").unwrap());
+        assert_eq!(document.font_size, 20.0);
+        assert!(document.is_inherited("colors.bg_color"));
+        assert!(results[1].is_ok());
+    }
+
+    #[test]
+    fn missing_base() {
+        let json = r#"{"id": 101, "name": "Dusk", "extends": "nope"}"#;
+        match resolve_themes(&[source(json)], &builtin_themes()).remove(0) {
+            Err(ThemeError::Invalid(message)) => assert!(message.contains("nope"), "{}", message),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn inheritance_cycle() {
+        let a = r#"{"id": 201, "name": "A", "extends": "b"}"#;
+        let b = r#"{"id": 202, "name": "B", "extends": "a"}"#;
+        let ok = r#"{"id": 203, "name": "C", "extends": "light-theme"}"#;
+        let results = resolve_themes(&[source(a), source(b), source(ok)], &builtin_themes());
+        let cycle = |result: &Result<ThemeDocument, ThemeError>| match result {
+            Err(ThemeError::InheritanceCycle(names)) => names.clone(),
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(cycle(&results[0]), vec!["A", "B", "A"]);
+        assert_eq!(cycle(&results[1]), vec!["B", "A", "B"]);
+        assert!(results[2].is_ok());
+    }
+
+    #[test]
+    fn override_field_marks_changes_only() {
+        let mut document = resolve_one(DUSK);
+        let fg_color = document.field("colors.fg_color").unwrap();
+        assert!(!document.override_field("colors.fg_color", fg_color));
+        assert!(document.is_inherited("colors.fg_color"));
+        assert!(document.override_field("colors.fg_color", FieldValue::Color(Color::RED)));
+        assert!(!document.is_inherited("colors.fg_color"));
+        assert!(document.override_field(NAME_FIELD, FieldValue::Text("Dawn".to_string())));
+        assert!(!document.overrides.contains(NAME_FIELD));
+    }
+
+    #[test]
+    fn rebase_copies_inherited_fields() {
+        let mut document = resolve_one(DUSK);
+        let mut base = dark();
+        base.colors.bg_color = Color::RED;
+        base.colors.fg_color = Color::GREEN;
+        document.rebase(&base).unwrap();
+        assert_eq!(document.colors.bg_color, parse_hex_color("#101820").unwrap());
+        assert_eq!(document.colors.fg_color, Color::GREEN);
+    }
+
+    #[test]
+    fn derived_theme_round_trip() {
+        let mut document = resolve_one(DUSK);
+        document.override_field("font_size", FieldValue::Number(18.0));
+        let json = document.to_json().unwrap();
+        assert!(!json.contains("fg_color"), "{}", json);
+        assert_eq!(resolve_one(&json), document);
+    }
+}
@@ -0,0 +1,395 @@
+/// A small JSON reader that keeps the line and column of every key and value. serde_json discards
+/// positions once a value is parsed, so this is used where errors need to point back into the file.
+/// It also reads JSONC (JSON with comments and trailing commas) as used by VS Code theme files.
+///
+use std::fmt;
+
+/// A position in the source text. Lines and columns start at 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextPos {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A JSON value with the position where it starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonNode {
+    pub pos: TextPos,
+    pub value: JsonValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonNode>),
+    /// Object members in source order. Duplicate keys are kept.
+    Object(Vec<JsonMember>),
+}
+
+/// A key/value pair in a JSON object
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonMember {
+    pub key: String,
+    pub key_pos: TextPos,
+    pub node: JsonNode,
+}
+
+impl JsonNode {
+    /// Look up a member of an object by key. Returns None if this is not an object.
+    pub fn get(&self, key: &str) -> Option<&JsonNode> {
+        match &self.value {
+            JsonValue::Object(members) => members.iter().rev().find(|m| m.key == key).map(|m| &m.node),
+            _ => None,
+        }
+    }
+
+    /// Convert to a serde_json Value, dropping positions
+    pub fn to_value(&self) -> serde_json::Value {
+        match &self.value {
+            JsonValue::Null => serde_json::Value::Null,
+            JsonValue::Bool(b) => serde_json::Value::Bool(*b),
+            JsonValue::Number(n) => serde_json::Number::from_f64(*n).map_or(serde_json::Value::Null, |n| n.into()),
+            JsonValue::String(s) => serde_json::Value::String(s.clone()),
+            JsonValue::Array(items) => serde_json::Value::Array(items.iter().map(|item| item.to_value()).collect()),
+            JsonValue::Object(members) => {
+                let map = members.iter().map(|m| (m.key.clone(), m.node.to_value())).collect();
+                serde_json::Value::Object(map)
+            }
+        }
+    }
+}
+
+impl JsonValue {
+    /// A short name for the type of value, used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            JsonValue::Null => "null",
+            JsonValue::Bool(_) => "boolean",
+            JsonValue::Number(_) => "number",
+            JsonValue::String(_) => "string",
+            JsonValue::Array(_) => "array",
+            JsonValue::Object(_) => "object",
+        }
+    }
+}
+
+/// A syntax error with the position where parsing stopped
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonSyntaxError {
+    pub pos: TextPos,
+    pub message: String,
+}
+
+impl fmt::Display for JsonSyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {} column {}", self.message, self.pos.line, self.pos.column)
+    }
+}
+
+/// Parse a JSON document
+pub fn parse_json(text: &str) -> Result<JsonNode, JsonSyntaxError> {
+    JsonReader::new(text, false).read_document()
+}
+
+/// Parse a JSONC document, which allows // and /* */ comments and trailing commas
+pub fn parse_jsonc(text: &str) -> Result<JsonNode, JsonSyntaxError> {
+    JsonReader::new(text, true).read_document()
+}
+
+struct JsonReader {
+    chars: Vec<char>,
+    index: usize,
+    line: usize,
+    column: usize,
+    /// Allow comments and trailing commas
+    jsonc: bool,
+}
+
+impl JsonReader {
+    fn new(text: &str, jsonc: bool) -> Self {
+        JsonReader { chars: text.chars().collect(), index: 0, line: 1, column: 1, jsonc }
+    }
+
+    fn read_document(&mut self) -> Result<JsonNode, JsonSyntaxError> {
+        self.skip_whitespace()?;
+        let node = self.read_value()?;
+        self.skip_whitespace()?;
+        if self.peek().is_some() {
+            return Err(self.error("Unexpected trailing characters"));
+        }
+        Ok(node)
+    }
+
+    fn pos(&self) -> TextPos {
+        TextPos { line: self.line, column: self.column }
+    }
+
+    fn error(&self, message: &str) -> JsonSyntaxError {
+        JsonSyntaxError { pos: self.pos(), message: message.to_string() }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).cloned()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonSyntaxError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.next();
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("Expected '{}' but found '{}'", expected, c))),
+            None => Err(self.error(&format!("Expected '{}' but found end of file", expected))),
+        }
+    }
+
+    /// Skip whitespace, and comments if reading JSONC
+    fn skip_whitespace(&mut self) -> Result<(), JsonSyntaxError> {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.next();
+            } else if self.jsonc && c == '/' && self.chars.get(self.index + 1) == Some(&'/') {
+                while let Some(c) = self.next() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else if self.jsonc && c == '/' && self.chars.get(self.index + 1) == Some(&'*') {
+                let start = self.pos();
+                self.next();
+                self.next();
+                loop {
+                    match self.next() {
+                        Some('*') if self.peek() == Some('/') => {
+                            self.next();
+                            break;
+                        }
+                        Some(_) => {}
+                        None => {
+                            return Err(JsonSyntaxError { pos: start, message: "Unterminated comment".to_string() });
+                        }
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// In JSONC, a comma may be followed by the closing bracket
+    fn at_trailing_close(&self, close: char) -> bool {
+        self.jsonc && self.peek() == Some(close)
+    }
+
+    fn read_value(&mut self) -> Result<JsonNode, JsonSyntaxError> {
+        let pos = self.pos();
+        let value = match self.peek() {
+            Some('{') => self.read_object()?,
+            Some('[') => self.read_array()?,
+            Some('"') => JsonValue::String(self.read_string()?),
+            Some('t') => self.read_literal("true", JsonValue::Bool(true))?,
+            Some('f') => self.read_literal("false", JsonValue::Bool(false))?,
+            Some('n') => self.read_literal("null", JsonValue::Null)?,
+            Some(c) if c == '-' || c.is_ascii_digit() => self.read_number()?,
+            Some(c) => return Err(self.error(&format!("Unexpected character '{}'", c))),
+            None => return Err(self.error("Unexpected end of file")),
+        };
+        Ok(JsonNode { pos, value })
+    }
+
+    fn read_object(&mut self) -> Result<JsonValue, JsonSyntaxError> {
+        self.expect('{')?;
+        let mut members: Vec<JsonMember> = Vec::new();
+        self.skip_whitespace()?;
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace()?;
+            if self.at_trailing_close('}') {
+                self.next();
+                break;
+            }
+            let key_pos = self.pos();
+            if self.peek() != Some('"') {
+                return Err(self.error("Expected a quoted object key"));
+            }
+            let key = self.read_string()?;
+            self.skip_whitespace()?;
+            self.expect(':')?;
+            self.skip_whitespace()?;
+            let node = self.read_value()?;
+            members.push(JsonMember { key, key_pos, node });
+            self.skip_whitespace()?;
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err(self.error("Expected ',' or '}' after object member")),
+            }
+        }
+        Ok(JsonValue::Object(members))
+    }
+
+    fn read_array(&mut self) -> Result<JsonValue, JsonSyntaxError> {
+        self.expect('[')?;
+        let mut items: Vec<JsonNode> = Vec::new();
+        self.skip_whitespace()?;
+        if self.peek() == Some(']') {
+            self.next();
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            self.skip_whitespace()?;
+            if self.at_trailing_close(']') {
+                self.next();
+                break;
+            }
+            items.push(self.read_value()?);
+            self.skip_whitespace()?;
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => break,
+                _ => return Err(self.error("Expected ',' or ']' after array item")),
+            }
+        }
+        Ok(JsonValue::Array(items))
+    }
+
+    fn read_string(&mut self) -> Result<String, JsonSyntaxError> {
+        self.expect('"')?;
+        let mut result = String::new();
+        loop {
+            match self.next() {
+                Some('"') => break,
+                Some('\\') => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.read_unicode_escape()?,
+                        _ => return Err(self.error("Invalid escape sequence")),
+                    };
+                    result.push(escaped);
+                }
+                Some(c) if (c as u32) < 0x20 => return Err(self.error("Control character in string")),
+                Some(c) => result.push(c),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+        Ok(result)
+    }
+
+    fn read_unicode_escape(&mut self) -> Result<char, JsonSyntaxError> {
+        let mut code: u32 = 0;
+        for _ in 0..4 {
+            let digit = self.next().and_then(|c| c.to_digit(16));
+            match digit {
+                Some(d) => code = code * 16 + d,
+                None => return Err(self.error("Invalid unicode escape")),
+            }
+        }
+        // Surrogate pairs are not needed for theme files and are replaced
+        Ok(std::char::from_u32(code).unwrap_or('\u{FFFD}'))
+    }
+
+    fn read_literal(&mut self, word: &str, value: JsonValue) -> Result<JsonValue, JsonSyntaxError> {
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error(&format!("Invalid literal, expected '{}'", word)));
+            }
+            self.next();
+        }
+        Ok(value)
+    }
+
+    fn read_number(&mut self) -> Result<JsonValue, JsonSyntaxError> {
+        let start = self.pos();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' {
+                text.push(c);
+                self.next();
+            } else {
+                break;
+            }
+        }
+        match text.parse::<f64>() {
+            Ok(number) => Ok(JsonValue::Number(number)),
+            Err(_) => Err(JsonSyntaxError { pos: start, message: format!("Invalid number '{}'", text) }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(node: &JsonNode) -> &[JsonMember] {
+        match &node.value {
+            JsonValue::Object(members) => members,
+            other => panic!("Expected an object but found {}", other.type_name()),
+        }
+    }
+
+    #[test]
+    fn positions_of_nested_values() {
+        let text = "{\n  \"colors\": {\n    \"bg_color\": \"#FFFFFF\"\n  }\n}";
+        let root = parse_json(text).unwrap();
+        assert_eq!(root.pos, TextPos { line: 1, column: 1 });
+        let colors = root.get("colors").unwrap();
+        assert_eq!(members(&root)[0].key_pos, TextPos { line: 2, column: 3 });
+        assert_eq!(colors.pos, TextPos { line: 2, column: 13 });
+        let bg_color = &members(colors)[0];
+        assert_eq!(bg_color.key_pos, TextPos { line: 3, column: 5 });
+        assert_eq!(bg_color.node.pos, TextPos { line: 3, column: 17 });
+        assert_eq!(bg_color.node.value, JsonValue::String("#FFFFFF".to_string()));
+    }
+
+    #[test]
+    fn jsonc_comments_and_trailing_commas() {
+        let text = "// comment\n{\n  /* block\n  comment */ \"name\": \"x\",\n}";
+        assert!(parse_json(text).is_err());
+        let root = parse_jsonc(text).unwrap();
+        assert_eq!(root.pos, TextPos { line: 2, column: 1 });
+        let name = &members(&root)[0];
+        assert_eq!(name.key_pos, TextPos { line: 4, column: 14 });
+        assert_eq!(name.node.pos, TextPos { line: 4, column: 22 });
+    }
+
+    #[test]
+    fn syntax_error_position() {
+        let err = parse_json("{\"a\": 1,, }").unwrap_err();
+        assert_eq!(err.pos, TextPos { line: 1, column: 9 });
+        let err = parse_json("{\n  \"a\": tru\n}").unwrap_err();
+        assert_eq!(err.pos.line, 2);
+    }
+
+    #[test]
+    fn duplicate_keys_are_kept() {
+        let root = parse_json("{\"a\": 1, \"a\": 2}").unwrap();
+        assert_eq!(members(&root).len(), 2);
+        assert_eq!(root.get("a").unwrap().value, JsonValue::Number(2.0));
+    }
+}
@@ -0,0 +1,39 @@
+pub use self::codegen::*;
+pub use self::color::*;
+pub use self::contrast::*;
+pub use self::css::*;
+pub use self::cvd::*;
+pub use self::diff::*;
+pub use self::document::*;
+pub use self::error::*;
+pub use self::expr::*;
+pub use self::field::*;
+pub use self::hex::*;
+pub use self::history::*;
+pub use self::inherit::*;
+pub use self::json::*;
+pub use self::palette::*;
+pub use self::recovery::*;
+pub use self::states::*;
+pub use self::validate::*;
+pub use self::vscode::*;
+
+mod codegen;
+mod color;
+mod contrast;
+mod css;
+mod cvd;
+mod diff;
+mod document;
+mod error;
+mod expr;
+mod field;
+mod hex;
+mod history;
+mod inherit;
+mod json;
+mod palette;
+mod recovery;
+mod states;
+mod validate;
+mod vscode;
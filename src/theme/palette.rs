@@ -0,0 +1,183 @@
+/// Palette generation. A full theme is made from one or two seed colors: the seed becomes the
+/// accent and the background, text and border colors are tinted with its hue. The highlight and
+/// cursor colors use a hue harmony of the seed, or the second seed if there is one. Colors are
+/// picked in OKLCH (see color.rs) so that lightness steps look even across hues, and every pair checked by
+/// contrast_report is adjusted until it reaches the minimum contrast.
+///
+use super::*;
+
+use std::fmt;
+
+use quicksilver::graphics::Color;
+use tweek::gui::Theme;
+
+/// Non-text pairs, such as borders and the cursor, need this contrast ratio
+const NON_TEXT_CONTRAST: f32 = 3.0;
+/// The OKLCH lightness step used when searching for a color with enough contrast
+const LIGHTNESS_STEP: f32 = 0.01;
+
+/// How the hues of the highlight and cursor colors relate to the seed hue
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Harmony {
+    /// The opposite hue
+    Complementary,
+    /// The neighbouring hues, 30 degrees either side
+    Analogous,
+    /// Three hues evenly spaced around the color wheel
+    Triadic,
+}
+
+impl Harmony {
+    pub const ALL: [Harmony; 3] = [Harmony::Complementary, Harmony::Analogous, Harmony::Triadic];
+
+    /// The hue offsets from the seed in degrees, for the highlight and cursor colors
+    pub fn hue_offsets(&self) -> (f32, f32) {
+        match self {
+            Harmony::Complementary => (180.0, 0.0),
+            Harmony::Analogous => (30.0, -30.0),
+            Harmony::Triadic => (120.0, 240.0),
+        }
+    }
+}
+
+impl fmt::Display for Harmony {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Harmony::Complementary => write!(f, "Complementary"),
+            Harmony::Analogous => write!(f, "Analogous"),
+            Harmony::Triadic => write!(f, "Triadic"),
+        }
+    }
+}
+
+/// Settings for palette generation
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteOptions {
+    pub harmony: Harmony,
+    /// Light text on a dark background
+    pub dark: bool,
+    /// The contrast ratio every text pair must reach. WCAG AA is 4.5.
+    pub min_contrast: f32,
+}
+
+impl Default for PaletteOptions {
+    fn default() -> Self {
+        PaletteOptions { harmony: Harmony::Complementary, dark: false, min_contrast: 4.5 }
+    }
+}
+
+impl PaletteOptions {
+    pub fn with_harmony(mut self, harmony: Harmony) -> Self {
+        self.harmony = harmony;
+        self
+    }
+
+    pub fn with_dark(mut self, dark: bool) -> Self {
+        self.dark = dark;
+        self
+    }
+
+    /// Builder method for the minimum text contrast. Values above 4.5 cannot always be reached
+    /// and are met as closely as black or white text allows.
+    pub fn with_min_contrast(mut self, ratio: f32) -> Self {
+        self.min_contrast = ratio;
+        self
+    }
+}
+
+/// The colors of a generated theme
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub background: Color,
+    pub foreground: Color,
+    /// The seed color adjusted for contrast, used for buttons
+    pub accent: Color,
+    /// The text color on accent backgrounds
+    pub accent_text: Color,
+    /// The pressed variant of the accent
+    pub pressed: Color,
+    /// The hover and selection highlight, from the harmony hue
+    pub highlight: Color,
+    pub border: Color,
+    pub cursor: Color,
+}
+
+impl Palette {
+    /// Generate a palette from a seed color. If a second seed is given, it is used for the highlight
+    /// and cursor colors instead of the harmony hues.
+    pub fn generate(seed: Color, second: Option<Color>, options: &PaletteOptions) -> Palette {
+        let seed_lch = Oklch::from_color(&seed);
+        let hue = seed_lch.h;
+        let (bg_lightness, fg_lightness) = if options.dark { (0.2, 0.92) } else { (0.98, 0.28) };
+        let min = options.min_contrast;
+
+        let background = Oklch { l: bg_lightness, c: 0.01, h: hue, alpha: 1.0 }.to_color();
+        let foreground = Oklch { l: fg_lightness, c: 0.02, h: hue, alpha: 1.0 }.to_color();
+        let foreground = ensure_contrast(foreground, &background, min);
+
+        // Button text shares the foreground so that the pressed color, which is also drawn under
+        // the foreground in lists, can contrast with both
+        let accent_text = foreground;
+        let accent = ensure_contrast(Color { a: 1.0, ..seed }, &accent_text, min);
+        let pressed = ensure_contrast(mix_srgb(&accent, &background, 0.5), &accent_text, min);
+
+        let (highlight_seed, cursor_seed) = match second {
+            Some(second) => (second, second),
+            None => {
+                let (highlight_offset, cursor_offset) = options.harmony.hue_offsets();
+                let chroma = seed_lch.c.max(0.08);
+                let lightness = if options.dark { 0.75 } else { 0.5 };
+                let harmony = Oklch { l: lightness, c: chroma, h: hue, alpha: 1.0 };
+                let highlight = Oklch { h: hue + highlight_offset, ..harmony }.to_color();
+                (highlight, Oklch { h: hue + cursor_offset, ..harmony }.to_color())
+            }
+        };
+        let highlight = ensure_contrast(highlight_seed, &background, min);
+        let cursor = ensure_contrast(cursor_seed, &background, NON_TEXT_CONTRAST);
+        let border = ensure_contrast(mix_srgb(&foreground, &background, 0.6), &background, NON_TEXT_CONTRAST);
+
+        Palette { background, foreground, accent, accent_text, pressed, highlight, border, cursor }
+    }
+
+    /// Make a theme document with the palette colors and default fonts and border
+    pub fn to_document(&self, id: u32, name: &str) -> ThemeDocument {
+        let mut document = ThemeDocument::from_theme(id, name, &Theme::default());
+        document.colors = ThemeColors {
+            bg_color: self.background,
+            fg_color: self.foreground,
+            border_color: self.border,
+            button_bg_color: self.accent,
+            button_fg_color: self.accent_text,
+            bg_pressed_color: self.pressed,
+            fg_highlight_color: self.highlight,
+            cursor_color: self.cursor,
+        };
+        document.border = ThemeBorder { style: BorderKind::Solid, width: 1.0 };
+        document
+    }
+}
+
+/// Change the lightness of a color until it has the contrast ratio against another color, moving
+/// away from the other color. Falls back to black or white if the ratio cannot be reached.
+pub fn ensure_contrast(color: Color, against: &Color, min_ratio: f32) -> Color {
+    if contrast_ratio(&color, against) >= min_ratio {
+        return color;
+    }
+    let mut lch = Oklch::from_color(&color);
+    // Move towards whichever of black or white has more contrast with the other color
+    let lighter = contrast_ratio(&Color::WHITE, against) >= contrast_ratio(&Color::BLACK, against);
+    while lch.l > 0.0 && lch.l < 1.0 {
+        lch.l = (lch.l + if lighter { LIGHTNESS_STEP } else { -LIGHTNESS_STEP }).max(0.0).min(1.0);
+        let candidate = lch.to_color();
+        if contrast_ratio(&candidate, against) >= min_ratio {
+            return candidate;
+        }
+    }
+    let extreme = if lighter { Color::WHITE } else { Color::BLACK };
+    Color { a: color.a, ..extreme }
+}
+
+/// Seed colors to choose from, with evenly spaced hues at the same OKLCH lightness and chroma
+pub fn seed_swatches(count: usize) -> Vec<Color> {
+    (0..count).map(|i| Oklch { l: 0.65, c: 0.15, h: 360.0 * i as f32 / count as f32, alpha: 1.0 }.to_color()).collect()
+}
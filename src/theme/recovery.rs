@@ -0,0 +1,162 @@
+/// A journal of unsaved theme edits for crash recovery. While there are unsaved changes, the editor
+/// writes them to RECOVERY_FILE_NAME in the themes directory every AUTOSAVE_INTERVAL_FRAMES, and removes
+/// the file when the changes are saved or discarded, or the app quits normally. So if the file exists at
+/// launch, the app did not shut down cleanly and the themes in it can be restored.
+///
+/// The journal holds each theme in its file form, so a derived theme is restored as derived:
+///
+/// {
+///   "version": 1,
+///   "themes": [ { "id": 100, "name": "My theme", "extends": "dark-theme", "font_size": 16.0 } ]
+/// }
+///
+use super::*;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The journal file in the themes directory. It does not end in .json, so Open does not load it as a theme.
+pub const RECOVERY_FILE_NAME: &str = "recovery.journal";
+/// How often unsaved changes are journaled. About five seconds at 60 FPS.
+pub const AUTOSAVE_INTERVAL_FRAMES: usize = 300;
+/// The current version of the journal format
+pub const RECOVERY_FORMAT_VERSION: u32 = 1;
+
+/// The unsaved themes written by the last autosave
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryJournal {
+    pub version: u32,
+    /// The contents of each theme file
+    pub themes: Vec<Value>,
+}
+
+impl RecoveryJournal {
+    /// Create a journal of the given themes
+    pub fn new(themes: &[ThemeDocument]) -> Result<Self, ThemeError> {
+        let mut values: Vec<Value> = Vec::new();
+        for theme in themes {
+            values.push(serde_json::from_str(&theme.to_json()?)?);
+        }
+        Ok(RecoveryJournal { version: RECOVERY_FORMAT_VERSION, themes: values })
+    }
+
+    /// Read the journal file. Returns None if there is no journal, which means the last session ended
+    /// cleanly or had nothing to recover.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Option<Self>, ThemeError> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let journal: RecoveryJournal = serde_json::from_str(&json)?;
+        if journal.version > RECOVERY_FORMAT_VERSION {
+            return Err(ThemeError::Invalid(format!("Unsupported journal version {}", journal.version)));
+        }
+        Ok(Some(journal))
+    }
+
+    /// Write the journal. It is written to a temporary file first and renamed, so a crash while writing
+    /// does not leave a partial journal.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ThemeError> {
+        let path = path.as_ref();
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    /// Delete the journal file. A missing file is not an error.
+    pub fn remove<P: AsRef<Path>>(path: P) -> Result<(), ThemeError> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The names of the journaled themes, for asking whether to restore them
+    pub fn theme_names(&self) -> Vec<String> {
+        self.themes.iter().map(|theme| theme["name"].as_str().unwrap_or_default().to_string()).collect()
+    }
+
+    /// Parse the journaled themes and resolve their inheritance against the registered themes. Returns
+    /// one result per theme, in journal order.
+    pub fn restore(&self, registered: &[ThemeDocument]) -> Vec<Result<ThemeDocument, ThemeError>> {
+        let parsed: Vec<Result<ThemeSource, ThemeError>> =
+            self.themes.iter().map(|theme| ThemeSource::from_json(&theme.to_string())).collect();
+        let sources: Vec<ThemeSource> = parsed.iter().filter_map(|source| source.as_ref().ok().cloned()).collect();
+        // resolve_themes returns one result per source, in order
+        let mut resolved = resolve_themes(&sources, registered).into_iter();
+        parsed.into_iter().map(|source| source.and_then(|_| resolved.next().expect("one result per source"))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    /// A journal path in a new directory for the test, so tests can run in parallel
+    fn journal_path(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tweek-theme-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(RECOVERY_FILE_NAME)
+    }
+
+    fn derived_theme() -> ThemeDocument {
+        let json = r##"{"id": 101, "name": "Dusk", "extends": "dark-theme", "colors": {"bg_color": "#101820"}}"##;
+        let source = ThemeSource::from_json(json).unwrap();
+        resolve_themes(&[source], &builtin_themes()).remove(0).unwrap()
+    }
+
+    #[test]
+    fn save_load_and_remove() {
+        let path = journal_path("save-load-remove");
+        let journal = RecoveryJournal::new(&[builtin_themes().remove(0), derived_theme()]).unwrap();
+        journal.save(&path).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+        assert_eq!(RecoveryJournal::load(&path).unwrap(), Some(journal));
+        RecoveryJournal::remove(&path).unwrap();
+        assert_eq!(RecoveryJournal::load(&path).unwrap(), None);
+        // Removing again is not an error
+        RecoveryJournal::remove(&path).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn restore_keeps_derived_themes_derived() {
+        let document = derived_theme();
+        let journal = RecoveryJournal::new(&[document.clone()]).unwrap();
+        assert_eq!(journal.theme_names(), vec!["Dusk"]);
+        // Only the overridden fields are journaled
+        assert!(journal.themes[0].get("font_size").is_none());
+        let restored = journal.restore(&builtin_themes()).remove(0).unwrap();
+        assert_eq!(restored.extends, Some("dark-theme".to_string()));
+        assert_eq!(restored, document);
+    }
+
+    #[test]
+    fn restore_reports_each_theme() {
+        let mut journal = RecoveryJournal::new(&[derived_theme()]).unwrap();
+        journal.themes.insert(0, serde_json::json!({ "id": 102, "name": "Broken", "extends": "nope" }));
+        let results = journal.restore(&builtin_themes());
+        assert!(results[0].is_err());
+        assert_eq!(results[1].as_ref().unwrap().name, "Dusk");
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let path = journal_path("unsupported-version");
+        let journal = RecoveryJournal { version: RECOVERY_FORMAT_VERSION + 1, themes: Vec::new() };
+        journal.save(&path).unwrap();
+        match RecoveryJournal::load(&path) {
+            Err(ThemeError::Invalid(message)) => assert!(message.contains("version"), "{}", message),
+            other => panic!("{:?}", other),
+        }
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+/// Interaction states of controls. A theme can override the colors drawn for each state with a "states"
+/// object, where every color is optional:
+///
+/// "states": {
+///   "hover": { "bg_color": "#EEF3FF" },
+///   "disabled": { "fg_color": "#A0A0A0", "border_color": "#E0E0E0" }
+/// }
+///
+/// Colors that are not overridden are derived from the theme colors, see ThemeDocument::state_style.
+///
+use super::*;
+
+use std::fmt;
+
+use quicksilver::graphics::Color;
+use serde::{Deserialize, Serialize};
+
+/// The key of the states object, also used as its override marker in derived themes
+pub const STATES_FIELD: &str = "states";
+/// The width of the border drawn around a focused control
+pub const FOCUS_BORDER_WIDTH: f32 = 2.0;
+
+/// An interaction state a control can be drawn in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlState {
+    /// The resting appearance, drawn with the theme colors
+    Normal,
+    Hover,
+    Pressed,
+    Focused,
+    Disabled,
+}
+
+impl ControlState {
+    pub const ALL: [ControlState; 5] = [
+        ControlState::Normal,
+        ControlState::Hover,
+        ControlState::Pressed,
+        ControlState::Focused,
+        ControlState::Disabled,
+    ];
+
+    /// The key of the state in the "states" object. Eg: hover
+    pub fn key(&self) -> &'static str {
+        match self {
+            ControlState::Normal => "normal",
+            ControlState::Hover => "hover",
+            ControlState::Pressed => "pressed",
+            ControlState::Focused => "focused",
+            ControlState::Disabled => "disabled",
+        }
+    }
+}
+
+impl fmt::Display for ControlState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ControlState::Normal => write!(f, "Normal"),
+            ControlState::Hover => write!(f, "Hover"),
+            ControlState::Pressed => write!(f, "Pressed"),
+            ControlState::Focused => write!(f, "Focused"),
+            ControlState::Disabled => write!(f, "Disabled"),
+        }
+    }
+}
+
+/// The colors a theme overrides for one state. None uses the derived color.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StateColors {
+    #[serde(default, with = "hex_color_option", skip_serializing_if = "Option::is_none")]
+    pub bg_color: Option<Color>,
+    #[serde(default, with = "hex_color_option", skip_serializing_if = "Option::is_none")]
+    pub fg_color: Option<Color>,
+    #[serde(default, with = "hex_color_option", skip_serializing_if = "Option::is_none")]
+    pub border_color: Option<Color>,
+}
+
+impl StateColors {
+    /// The names of all state color fields, in file order
+    pub const FIELDS: [&'static str; 3] = ["bg_color", "fg_color", "border_color"];
+
+    pub fn is_empty(&self) -> bool {
+        self.bg_color.is_none() && self.fg_color.is_none() && self.border_color.is_none()
+    }
+
+    /// Get an overridden color by field name
+    pub fn get(&self, field: &str) -> Option<Color> {
+        match field {
+            "bg_color" => self.bg_color,
+            "fg_color" => self.fg_color,
+            "border_color" => self.border_color,
+            _ => None,
+        }
+    }
+
+    /// Set or clear a color override by field name. Returns false if there is no such field.
+    pub fn set(&mut self, field: &str, color: Option<Color>) -> bool {
+        match field {
+            "bg_color" => self.bg_color = color,
+            "fg_color" => self.fg_color = color,
+            "border_color" => self.border_color = color,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// The per-state color overrides of a theme. The normal state always uses the theme colors.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeStates {
+    #[serde(default, skip_serializing_if = "StateColors::is_empty")]
+    pub hover: StateColors,
+    #[serde(default, skip_serializing_if = "StateColors::is_empty")]
+    pub pressed: StateColors,
+    #[serde(default, skip_serializing_if = "StateColors::is_empty")]
+    pub focused: StateColors,
+    #[serde(default, skip_serializing_if = "StateColors::is_empty")]
+    pub disabled: StateColors,
+}
+
+impl ThemeStates {
+    pub fn is_empty(&self) -> bool {
+        self.hover.is_empty() && self.pressed.is_empty() && self.focused.is_empty() && self.disabled.is_empty()
+    }
+
+    /// The overrides of a state, or None for the normal state
+    pub fn get(&self, state: ControlState) -> Option<&StateColors> {
+        match state {
+            ControlState::Normal => None,
+            ControlState::Hover => Some(&self.hover),
+            ControlState::Pressed => Some(&self.pressed),
+            ControlState::Focused => Some(&self.focused),
+            ControlState::Disabled => Some(&self.disabled),
+        }
+    }
+
+    pub fn get_mut(&mut self, state: ControlState) -> Option<&mut StateColors> {
+        match state {
+            ControlState::Normal => None,
+            ControlState::Hover => Some(&mut self.hover),
+            ControlState::Pressed => Some(&mut self.pressed),
+            ControlState::Focused => Some(&mut self.focused),
+            ControlState::Disabled => Some(&mut self.disabled),
+        }
+    }
+}
+
+/// The resolved colors and border width of a control in a state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StateStyle {
+    pub bg_color: Color,
+    pub fg_color: Color,
+    pub border_color: Color,
+    pub border_width: f32,
+}
+
+impl ThemeDocument {
+    /// The style of a control in a state. Overridden colors are used as they are, and the others are
+    /// derived from the theme colors:
+    /// * Hover: the background tinted with the highlight color and a highlight border
+    /// * Pressed: the pressed background
+    /// * Focused: a highlight border FOCUS_BORDER_WIDTH wide
+    /// * Disabled: text and border faded halfway into the background
+    pub fn state_style(&self, state: ControlState) -> StateStyle {
+        let colors = &self.colors;
+        let border_width = match self.border.style {
+            BorderKind::None => 0.0,
+            BorderKind::Solid => self.border.width,
+        };
+        let derived = match state {
+            ControlState::Normal => StateStyle {
+                bg_color: colors.bg_color,
+                fg_color: colors.fg_color,
+                border_color: colors.border_color,
+                border_width,
+            },
+            ControlState::Hover => StateStyle {
+                bg_color: mix_srgb(&colors.bg_color, &colors.fg_highlight_color, 0.15),
+                fg_color: colors.fg_color,
+                border_color: colors.fg_highlight_color,
+                border_width: border_width.max(1.0),
+            },
+            ControlState::Pressed => StateStyle {
+                bg_color: colors.bg_pressed_color,
+                fg_color: colors.fg_color,
+                border_color: colors.border_color,
+                border_width,
+            },
+            ControlState::Focused => StateStyle {
+                bg_color: colors.bg_color,
+                fg_color: colors.fg_color,
+                border_color: colors.fg_highlight_color,
+                border_width: FOCUS_BORDER_WIDTH,
+            },
+            ControlState::Disabled => StateStyle {
+                bg_color: colors.bg_color,
+                fg_color: mix_srgb(&colors.fg_color, &colors.bg_color, 0.5),
+                border_color: mix_srgb(&colors.border_color, &colors.bg_color, 0.5),
+                border_width,
+            },
+        };
+        match self.states.get(state) {
+            Some(overrides) => StateStyle {
+                bg_color: overrides.bg_color.unwrap_or(derived.bg_color),
+                fg_color: overrides.fg_color.unwrap_or(derived.fg_color),
+                border_color: overrides.border_color.unwrap_or(derived.border_color),
+                ..derived
+            },
+            None => derived,
+        }
+    }
+}
+
+/// Serde adapter for optional Color fields. Use with #[serde(default, with = "hex_color_option")]
+pub mod hex_color_option {
+    use super::*;
+
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S>(color: &Option<Color>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match color {
+            Some(color) => hex_color::serialize(color, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Color>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        hex_color::deserialize(deserializer).map(Some)
+    }
+}
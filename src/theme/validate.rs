@@ -0,0 +1,410 @@
+/// Validation of theme files. Unlike serde, which stops at the first problem, the validator walks the
+/// whole document and reports every error with its JSON path and position in the file.
+///
+use super::*;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+/// The kind of problem found by the validator
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationErrorKind {
+    /// The file is not valid JSON
+    Syntax,
+    /// An object contains a key that is not part of the format
+    UnknownKey,
+    /// An object contains the same key more than once
+    DuplicateKey,
+    /// A required key is missing
+    MissingField,
+    /// A value has the wrong JSON type
+    WrongType,
+    /// A string is not a valid hex color
+    InvalidColor,
+    /// A value has the right type but is out of range or not one of the allowed values
+    InvalidValue,
+    /// A color expression cannot be parsed, or is part of a cycle
+    InvalidExpression,
+}
+
+/// A single problem in a theme file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationError {
+    /// The JSON path of the offending value. Eg: $.colors.bg_color
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub kind: ValidationErrorKind,
+    pub message: String,
+}
+
+impl ValidationError {
+    fn new(path: &str, pos: TextPos, kind: ValidationErrorKind, message: String) -> Self {
+        ValidationError { path: path.to_string(), line: pos.line, column: pos.column, kind, message }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{} {}: {}", self.line, self.column, self.path, self.message)
+    }
+}
+
+/// The expected type of a value in a theme file
+#[derive(Debug, Clone, Copy)]
+pub enum FieldKind {
+    /// A whole number >= 0
+    Integer,
+    Text,
+    /// A number > 0
+    PositiveNumber,
+    /// A number >= 0
+    NonNegativeNumber,
+    /// A hex color string
+    Color,
+    /// A string which must be one of the listed values
+    Choice(&'static [&'static str]),
+    Object(&'static [FieldSpec]),
+    /// An object of color expressions keyed by color field name
+    Expressions,
+}
+
+/// Whether a key must be present in a theme file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Presence {
+    Optional,
+    /// Required, except in themes that extend another theme
+    Required,
+    /// Required in every theme file
+    Always,
+}
+
+/// Describes one key of a JSON object in a theme file
+#[derive(Debug, Clone, Copy)]
+pub struct FieldSpec {
+    pub name: &'static str,
+    pub kind: FieldKind,
+    pub presence: Presence,
+}
+
+impl FieldSpec {
+    const fn required(name: &'static str, kind: FieldKind) -> Self {
+        FieldSpec { name, kind, presence: Presence::Required }
+    }
+
+    const fn optional(name: &'static str, kind: FieldKind) -> Self {
+        FieldSpec { name, kind, presence: Presence::Optional }
+    }
+
+    const fn always(name: &'static str, kind: FieldKind) -> Self {
+        FieldSpec { name, kind, presence: Presence::Always }
+    }
+
+    /// True if the field must be present. Derived themes only store the fields they override.
+    fn is_required(&self, derived: bool) -> bool {
+        match self.presence {
+            Presence::Optional => false,
+            Presence::Required => !derived,
+            Presence::Always => true,
+        }
+    }
+}
+
+const FONTS_SCHEMA: &[FieldSpec] =
+    &[FieldSpec::required("default", FieldKind::Text), FieldSpec::required("title", FieldKind::Text)];
+
+const COLORS_SCHEMA: &[FieldSpec] = &[
+    FieldSpec::required("bg_color", FieldKind::Color),
+    FieldSpec::required("fg_color", FieldKind::Color),
+    FieldSpec::required("border_color", FieldKind::Color),
+    FieldSpec::required("button_bg_color", FieldKind::Color),
+    FieldSpec::required("button_fg_color", FieldKind::Color),
+    FieldSpec::required("bg_pressed_color", FieldKind::Color),
+    FieldSpec::required("fg_highlight_color", FieldKind::Color),
+    FieldSpec::required("cursor_color", FieldKind::Color),
+];
+
+const BORDER_SCHEMA: &[FieldSpec] = &[
+    FieldSpec::required("style", FieldKind::Choice(&["none", "solid"])),
+    FieldSpec::required("width", FieldKind::NonNegativeNumber),
+];
+
+const STATE_COLORS_SCHEMA: &[FieldSpec] = &[
+    FieldSpec::optional("bg_color", FieldKind::Color),
+    FieldSpec::optional("fg_color", FieldKind::Color),
+    FieldSpec::optional("border_color", FieldKind::Color),
+];
+
+const STATES_SCHEMA: &[FieldSpec] = &[
+    FieldSpec::optional("hover", FieldKind::Object(STATE_COLORS_SCHEMA)),
+    FieldSpec::optional("pressed", FieldKind::Object(STATE_COLORS_SCHEMA)),
+    FieldSpec::optional("focused", FieldKind::Object(STATE_COLORS_SCHEMA)),
+    FieldSpec::optional("disabled", FieldKind::Object(STATE_COLORS_SCHEMA)),
+];
+
+/// The schema of a theme file. This must be kept in sync with ThemeDocument.
+pub const THEME_SCHEMA: &[FieldSpec] = &[
+    FieldSpec::optional("version", FieldKind::Integer),
+    FieldSpec::always("id", FieldKind::Integer),
+    FieldSpec::always("name", FieldKind::Text),
+    FieldSpec::optional("extends", FieldKind::Text),
+    FieldSpec::required("font_size", FieldKind::PositiveNumber),
+    FieldSpec::required("fonts", FieldKind::Object(FONTS_SCHEMA)),
+    FieldSpec::required("colors", FieldKind::Object(COLORS_SCHEMA)),
+    FieldSpec::required("border", FieldKind::Object(BORDER_SCHEMA)),
+    FieldSpec::optional("expressions", FieldKind::Expressions),
+    FieldSpec::optional("states", FieldKind::Object(STATES_SCHEMA)),
+];
+
+/// Validate the text of a theme file and return every problem found. An empty list means the
+/// text can be loaded with ThemeSource::from_json. Themes with an "extends" key only need the
+/// id and name, since other fields are inherited from the base theme.
+pub fn validate_theme_json(text: &str) -> Vec<ValidationError> {
+    let root = match parse_json(text) {
+        Ok(root) => root,
+        Err(err) => {
+            return vec![ValidationError::new("$", err.pos, ValidationErrorKind::Syntax, err.message)];
+        }
+    };
+    let derived = root.get("extends").is_some();
+    let mut errors: Vec<ValidationError> = Vec::new();
+    check_object(&root, THEME_SCHEMA, "$", derived, &mut errors);
+
+    if let JsonValue::Object(members) = &root.value {
+        let version = members.iter().find(|m| m.key == "version");
+        if let Some(JsonMember { node: JsonNode { pos, value: JsonValue::Number(n) }, .. }) = version {
+            if *n > THEME_FORMAT_VERSION as f64 {
+                let message = format!("Unsupported format version {}, expected {}", n, THEME_FORMAT_VERSION);
+                errors.push(ValidationError::new("$.version", *pos, ValidationErrorKind::InvalidValue, message));
+            }
+        }
+    }
+    errors
+}
+
+/// Validate a theme file on disk. This is the headless entry point used by the validate command.
+pub fn validate_theme_file<P: AsRef<Path>>(path: P) -> Result<Vec<ValidationError>, ThemeError> {
+    let text = fs::read_to_string(path)?;
+    Ok(validate_theme_json(&text))
+}
+
+fn check_object(node: &JsonNode, fields: &[FieldSpec], path: &str, derived: bool, errors: &mut Vec<ValidationError>) {
+    let members = match &node.value {
+        JsonValue::Object(members) => members,
+        other => {
+            let message = format!("Expected an object but found {}", other.type_name());
+            errors.push(ValidationError::new(path, node.pos, ValidationErrorKind::WrongType, message));
+            return;
+        }
+    };
+    let mut seen: Vec<&str> = Vec::new();
+    for member in members {
+        let member_path = format!("{}.{}", path, member.key);
+        if seen.contains(&member.key.as_str()) {
+            let message = format!("Duplicate key {:?}", member.key);
+            let kind = ValidationErrorKind::DuplicateKey;
+            errors.push(ValidationError::new(&member_path, member.key_pos, kind, message));
+            continue;
+        }
+        seen.push(&member.key);
+        match fields.iter().find(|f| f.name == member.key) {
+            Some(field) => check_value(&member.node, field.kind, &member_path, derived, errors),
+            None => {
+                let message = format!("Unknown key {:?}", member.key);
+                let kind = ValidationErrorKind::UnknownKey;
+                errors.push(ValidationError::new(&member_path, member.key_pos, kind, message));
+            }
+        }
+    }
+    for field in fields.iter().filter(|f| f.is_required(derived) && !seen.contains(&f.name)) {
+        let field_path = format!("{}.{}", path, field.name);
+        let message = format!("Missing required field {:?}", field.name);
+        errors.push(ValidationError::new(&field_path, node.pos, ValidationErrorKind::MissingField, message));
+    }
+}
+
+fn check_value(node: &JsonNode, kind: FieldKind, path: &str, derived: bool, errors: &mut Vec<ValidationError>) {
+    let wrong_type = |expected: &str| {
+        let message = format!("Expected {} but found {}", expected, node.value.type_name());
+        ValidationError::new(path, node.pos, ValidationErrorKind::WrongType, message)
+    };
+    let invalid = |message: String| ValidationError::new(path, node.pos, ValidationErrorKind::InvalidValue, message);
+
+    match (kind, &node.value) {
+        (FieldKind::Object(fields), _) => check_object(node, fields, path, derived, errors),
+        (FieldKind::Expressions, _) => check_expressions(node, path, errors),
+        (FieldKind::Integer, JsonValue::Number(n)) => {
+            if *n < 0.0 || n.fract() != 0.0 || *n > u32::max_value() as f64 {
+                errors.push(invalid(format!("Expected a whole number >= 0 but found {}", n)));
+            }
+        }
+        (FieldKind::PositiveNumber, JsonValue::Number(n)) => {
+            if *n <= 0.0 {
+                errors.push(invalid(format!("Expected a number greater than 0 but found {}", n)));
+            }
+        }
+        (FieldKind::NonNegativeNumber, JsonValue::Number(n)) => {
+            if *n < 0.0 {
+                errors.push(invalid(format!("Expected a number >= 0 but found {}", n)));
+            }
+        }
+        (FieldKind::Text, JsonValue::String(_)) => {}
+        (FieldKind::Color, JsonValue::String(s)) => {
+            if parse_hex_color(s).is_none() {
+                let message = format!("Invalid hex color {:?}, expected #RRGGBB or #RRGGBBAA", s);
+                errors.push(ValidationError::new(path, node.pos, ValidationErrorKind::InvalidColor, message));
+            }
+        }
+        (FieldKind::Choice(choices), JsonValue::String(s)) => {
+            if !choices.contains(&s.as_str()) {
+                errors.push(invalid(format!("Expected one of {:?} but found {:?}", choices, s)));
+            }
+        }
+        (FieldKind::Integer, _) | (FieldKind::PositiveNumber, _) | (FieldKind::NonNegativeNumber, _) => {
+            errors.push(wrong_type("a number"))
+        }
+        (FieldKind::Text, _) | (FieldKind::Color, _) | (FieldKind::Choice(_), _) => errors.push(wrong_type("a string")),
+    }
+}
+
+fn check_expressions(node: &JsonNode, path: &str, errors: &mut Vec<ValidationError>) {
+    let members = match &node.value {
+        JsonValue::Object(members) => members,
+        other => {
+            let message = format!("Expected an object but found {}", other.type_name());
+            errors.push(ValidationError::new(path, node.pos, ValidationErrorKind::WrongType, message));
+            return;
+        }
+    };
+    let mut parsed: BTreeMap<String, Expr> = BTreeMap::new();
+    for member in members {
+        let member_path = format!("{}.{}", path, member.key);
+        if parsed.contains_key(&member.key) {
+            let message = format!("Duplicate key {:?}", member.key);
+            let kind = ValidationErrorKind::DuplicateKey;
+            errors.push(ValidationError::new(&member_path, member.key_pos, kind, message));
+            continue;
+        }
+        if !ThemeColors::FIELDS.contains(&member.key.as_str()) {
+            let message = format!("Unknown color field {:?}", member.key);
+            let kind = ValidationErrorKind::UnknownKey;
+            errors.push(ValidationError::new(&member_path, member.key_pos, kind, message));
+            continue;
+        }
+        let text = match &member.node.value {
+            JsonValue::String(text) => text,
+            other => {
+                let message = format!("Expected a string but found {}", other.type_name());
+                let kind = ValidationErrorKind::WrongType;
+                errors.push(ValidationError::new(&member_path, member.node.pos, kind, message));
+                continue;
+            }
+        };
+        match parse_expr(text) {
+            Ok(expr) => {
+                parsed.insert(member.key.clone(), expr);
+            }
+            Err((_, message)) => {
+                let kind = ValidationErrorKind::InvalidExpression;
+                errors.push(ValidationError::new(&member_path, member.node.pos, kind, message));
+            }
+        }
+    }
+    let (_, cycle_errors) = expression_order(&parsed);
+    for error in cycle_errors {
+        if let Some(member) = members.iter().find(|m| m.key == error.field) {
+            let member_path = format!("{}.{}", path, member.key);
+            let kind = ValidationErrorKind::InvalidExpression;
+            errors.push(ValidationError::new(&member_path, member.node.pos, kind, error.message));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(errors: &[ValidationError]) -> Vec<(&str, ValidationErrorKind)> {
+        errors.iter().map(|error| (error.path.as_str(), error.kind)).collect()
+    }
+
+    #[test]
+    fn valid_derived_theme() {
+        let text = r##"{"id": 101, "name": "Dusk", "extends": "dark-theme", "colors": {"bg_color": "#101820"}}"##;
+        assert_eq!(validate_theme_json(text), Vec::new());
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let text = r#"{"id": 100, "name": "x", "extends": "dark-theme", "font_size": "big",
+            "colors": {"bg_color": "nope"}, "colour": 1}"#;
+        let errors = validate_theme_json(text);
+        assert_eq!(
+            kinds(&errors),
+            vec![
+                ("$.font_size", ValidationErrorKind::WrongType),
+                ("$.colors.bg_color", ValidationErrorKind::InvalidColor),
+                ("$.colour", ValidationErrorKind::UnknownKey),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_fields_of_complete_theme() {
+        let errors = validate_theme_json(r#"{"id": 1, "name": "x"}"#);
+        assert_eq!(
+            kinds(&errors),
+            vec![
+                ("$.font_size", ValidationErrorKind::MissingField),
+                ("$.fonts", ValidationErrorKind::MissingField),
+                ("$.colors", ValidationErrorKind::MissingField),
+                ("$.border", ValidationErrorKind::MissingField),
+            ]
+        );
+    }
+
+    #[test]
+    fn positions_of_nested_errors() {
+        let text = "{\n  \"id\": 100,\n  \"name\": \"x\",\n  \"extends\": \"dark-theme\",\n  \"colors\": {\n    \
+                    \"bg_color\": 5,\n    \"bg_colour\": \"#FFFFFF\"\n  }\n}";
+        let errors = validate_theme_json(text);
+        assert_eq!(
+            kinds(&errors),
+            vec![
+                ("$.colors.bg_color", ValidationErrorKind::WrongType),
+                ("$.colors.bg_colour", ValidationErrorKind::UnknownKey),
+            ]
+        );
+        // Type errors point at the value and unknown keys at the key
+        assert_eq!((errors[0].line, errors[0].column), (6, 17));
+        assert_eq!((errors[1].line, errors[1].column), (7, 5));
+    }
+
+    #[test]
+    fn type_and_value_errors() {
+        let text = r#"{"id": "abc", "name": 5, "extends": "dark-theme", "font_size": -1,
+            "border": {"style": "dotted", "width": 1}}"#;
+        let errors = validate_theme_json(text);
+        assert_eq!(
+            kinds(&errors),
+            vec![
+                ("$.id", ValidationErrorKind::WrongType),
+                ("$.name", ValidationErrorKind::WrongType),
+                ("$.font_size", ValidationErrorKind::InvalidValue),
+                ("$.border.style", ValidationErrorKind::InvalidValue),
+            ]
+        );
+    }
+
+    #[test]
+    fn syntax_error() {
+        let errors = validate_theme_json("{\"id\": 1,\n  \"name\": }");
+        assert_eq!(kinds(&errors), vec![("$", ValidationErrorKind::Syntax)]);
+        assert_eq!((errors[0].line, errors[0].column), (2, 11));
+    }
+}
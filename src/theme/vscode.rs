@@ -0,0 +1,336 @@
+/// Import and export of VS Code color themes. A VS Code theme has a "colors" object of workbench colors and a
+/// "tokenColors" array of syntax highlighting rules. VscodeMapping decides which of these fill each
+/// color of a Tweek theme, and any color that cannot be found keeps the value of a base theme.
+///
+use super::*;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use quicksilver::graphics::Color;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// The file name suffix used by VS Code color themes. Eg: "Night Owl-color-theme.json"
+pub const VSCODE_THEME_SUFFIX: &str = "-color-theme.json";
+/// Prefix for mapping sources that read a tokenColors scope rather than a workbench color
+pub const TOKEN_SOURCE_PREFIX: &str = "token:";
+
+/// Workbench color keys that a complete VS Code theme is expected to define. The exporter reports the
+/// keys in this list which the mapping does not fill.
+pub const VSCODE_WORKBENCH_KEYS: &[&str] = &[
+    "foreground",
+    "focusBorder",
+    "contrastBorder",
+    "editor.background",
+    "editor.foreground",
+    "editor.selectionBackground",
+    "editorCursor.foreground",
+    "editorLineNumber.foreground",
+    "editorGroup.border",
+    "button.background",
+    "button.foreground",
+    "input.background",
+    "input.foreground",
+    "input.border",
+    "list.activeSelectionBackground",
+    "list.activeSelectionForeground",
+    "list.hoverBackground",
+    "panel.border",
+    "sideBar.background",
+    "sideBar.foreground",
+    "activityBar.background",
+    "activityBar.foreground",
+    "statusBar.background",
+    "statusBar.foreground",
+    "titleBar.activeBackground",
+    "titleBar.activeForeground",
+    "tab.activeBackground",
+    "tab.inactiveBackground",
+    "textLink.foreground",
+];
+
+/// Maps each Tweek theme color field to a list of VS Code sources in priority order. A source is
+/// either a workbench color key such as "editor.background", or a tokenColors scope prefixed with
+/// "token:" such as "token:keyword". The mapping file is a JSON object in the same shape. Eg:
+///
+/// { "bg_color": ["editor.background"], "fg_highlight_color": ["focusBorder", "token:keyword"] }
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VscodeMapping {
+    pub fields: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for VscodeMapping {
+    fn default() -> Self {
+        let table: &[(&str, &[&str])] = &[
+            ("bg_color", &["editor.background"]),
+            ("fg_color", &["editor.foreground", "foreground"]),
+            ("border_color", &["input.border", "panel.border", "editorGroup.border", "contrastBorder"]),
+            ("button_bg_color", &["button.background"]),
+            ("button_fg_color", &["button.foreground"]),
+            ("bg_pressed_color", &["list.activeSelectionBackground", "editor.selectionBackground"]),
+            ("fg_highlight_color", &["focusBorder", "textLink.foreground", "token:keyword"]),
+            ("cursor_color", &["editorCursor.foreground"]),
+        ];
+        let fields = table
+            .iter()
+            .map(|(field, sources)| (field.to_string(), sources.iter().map(|s| s.to_string()).collect()))
+            .collect();
+        VscodeMapping { fields }
+    }
+}
+
+impl VscodeMapping {
+    /// Read a mapping file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ThemeError> {
+        let json = fs::read_to_string(path)?;
+        let mapping: VscodeMapping = serde_json::from_str(&json)?;
+        for field in mapping.fields.keys() {
+            if !ThemeColors::FIELDS.contains(&field.as_str()) {
+                return Err(ThemeError::Invalid(format!("Unknown theme color {:?} in mapping", field)));
+            }
+        }
+        Ok(mapping)
+    }
+
+    /// Replace the sources for a theme color field
+    pub fn set(&mut self, field: &str, sources: &[&str]) {
+        self.fields.insert(field.to_string(), sources.iter().map(|s| s.to_string()).collect());
+    }
+
+    /// The sources for a theme color field, in priority order
+    pub fn sources(&self, field: &str) -> &[String] {
+        match self.fields.get(field) {
+            Some(sources) => sources,
+            None => &[],
+        }
+    }
+}
+
+/// The "type" of a VS Code theme
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VscodeThemeKind {
+    Light,
+    Dark,
+    HighContrast,
+}
+
+/// A syntax highlighting rule from tokenColors
+#[derive(Debug, Clone, PartialEq)]
+pub struct VscodeTokenColor {
+    pub scopes: Vec<String>,
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub font_style: Option<String>,
+}
+
+/// The parts of a VS Code color theme used for import
+#[derive(Debug, Clone, PartialEq)]
+pub struct VscodeTheme {
+    pub name: String,
+    pub kind: VscodeThemeKind,
+    pub colors: BTreeMap<String, Color>,
+    pub token_colors: Vec<VscodeTokenColor>,
+}
+
+/// The result of converting a VS Code theme
+#[derive(Debug, Clone)]
+pub struct VscodeImport {
+    pub document: ThemeDocument,
+    /// Theme color fields that no mapping source could fill. These keep the base theme value.
+    pub unmapped: Vec<String>,
+}
+
+impl VscodeTheme {
+    /// Parse the text of a VS Code color theme. Comments and trailing commas are allowed.
+    /// Colors that are not valid hex strings are skipped.
+    pub fn parse(text: &str) -> Result<Self, ThemeError> {
+        let root = parse_jsonc(text).map_err(|err| ThemeError::Invalid(err.to_string()))?.to_value();
+        if !root.is_object() {
+            return Err(ThemeError::Invalid("A VS Code theme must be a JSON object".to_string()));
+        }
+        let name = root["name"].as_str().unwrap_or("Untitled").to_string();
+
+        let mut colors: BTreeMap<String, Color> = BTreeMap::new();
+        if let Some(map) = root["colors"].as_object() {
+            for (key, value) in map {
+                match value.as_str().and_then(parse_hex_color) {
+                    Some(color) => {
+                        colors.insert(key.clone(), color);
+                    }
+                    None => log::warn!("Skipping VS Code color {:?} with value {}", key, value),
+                }
+            }
+        }
+
+        // tokenColors can also be a path to a tmTheme file, which is not supported
+        let mut token_colors: Vec<VscodeTokenColor> = Vec::new();
+        if let Some(rules) = root["tokenColors"].as_array() {
+            for rule in rules {
+                let scopes: Vec<String> = match &rule["scope"] {
+                    serde_json::Value::String(s) => s.split(',').map(|s| s.trim().to_string()).collect(),
+                    serde_json::Value::Array(items) => {
+                        items.iter().filter_map(|s| s.as_str()).map(|s| s.trim().to_string()).collect()
+                    }
+                    _ => Vec::new(),
+                };
+                let settings = &rule["settings"];
+                token_colors.push(VscodeTokenColor {
+                    scopes,
+                    foreground: settings["foreground"].as_str().and_then(parse_hex_color),
+                    background: settings["background"].as_str().and_then(parse_hex_color),
+                    font_style: settings["fontStyle"].as_str().map(|s| s.to_string()),
+                });
+            }
+        }
+
+        let kind = match root["type"].as_str() {
+            Some("light") => VscodeThemeKind::Light,
+            Some("dark") => VscodeThemeKind::Dark,
+            Some(t) if t.starts_with("hc") => VscodeThemeKind::HighContrast,
+            // Without a type, guess from the editor background
+            _ => match colors.get("editor.background") {
+                Some(bg) if !is_dark_color(bg) => VscodeThemeKind::Light,
+                _ => VscodeThemeKind::Dark,
+            },
+        };
+
+        Ok(VscodeTheme { name, kind, colors, token_colors })
+    }
+
+    /// Read a VS Code color theme file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ThemeError> {
+        let text = fs::read_to_string(path)?;
+        VscodeTheme::parse(&text)
+    }
+
+    /// True for dark and high contrast themes
+    pub fn is_dark(&self) -> bool {
+        self.kind != VscodeThemeKind::Light
+    }
+
+    /// Find the color for a mapping source. For tokenColors the last matching rule wins, as in VS Code.
+    /// A rule matches if it has the same scope or a more specific one. Eg: "keyword" matches "keyword.control"
+    pub fn lookup(&self, source: &str) -> Option<Color> {
+        if source.starts_with(TOKEN_SOURCE_PREFIX) {
+            let scope = &source[TOKEN_SOURCE_PREFIX.len()..];
+            let prefix = format!("{}.", scope);
+            self.token_colors
+                .iter()
+                .rev()
+                .filter(|rule| rule.scopes.iter().any(|s| s == scope || s.starts_with(&prefix)))
+                .find_map(|rule| rule.foreground)
+        } else {
+            self.colors.get(source).cloned()
+        }
+    }
+
+    /// Convert to a theme document. Colors that cannot be found using the mapping keep the value
+    /// from the base document, which should be a light or dark theme to match is_dark().
+    pub fn to_document(&self, id: u32, base: &ThemeDocument, mapping: &VscodeMapping) -> VscodeImport {
+        let mut document = base.clone();
+        document.id = id;
+        document.name = self.name.clone();
+        let mut unmapped: Vec<String> = Vec::new();
+        for field in ThemeColors::FIELDS.iter() {
+            match mapping.sources(field).iter().find_map(|source| self.lookup(source)) {
+                Some(color) => {
+                    document.colors.set(field, color);
+                }
+                None => unmapped.push(field.to_string()),
+            }
+        }
+        VscodeImport { document, unmapped }
+    }
+}
+
+/// The result of exporting a theme as a VS Code color theme
+#[derive(Debug, Clone)]
+pub struct VscodeExport {
+    pub json: Value,
+    /// Keys from VSCODE_WORKBENCH_KEYS which no theme color maps to
+    pub unfilled: Vec<String>,
+}
+
+impl VscodeExport {
+    /// The theme as pretty printed JSON
+    pub fn to_json(&self) -> Result<String, ThemeError> {
+        let json = serde_json::to_string_pretty(&self.json)?;
+        Ok(json)
+    }
+
+    /// Write the theme to a file, replacing any existing file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ThemeError> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+/// Export a theme document as a VS Code color theme. Every source in the mapping is written with the
+/// color of its theme field, so importing the result with the same mapping gives back the same colors.
+/// If two fields share a source, the field listed first in ThemeColors::FIELDS wins.
+pub fn export_vscode_theme(document: &ThemeDocument, mapping: &VscodeMapping) -> VscodeExport {
+    let mut colors = Map::new();
+    let mut token_colors: Vec<Value> = Vec::new();
+    let mut scopes: Vec<String> = Vec::new();
+    for field in ThemeColors::FIELDS.iter() {
+        let color = match document.colors.get(field) {
+            Some(color) => color,
+            None => continue,
+        };
+        for source in mapping.sources(field) {
+            if source.starts_with(TOKEN_SOURCE_PREFIX) {
+                let scope = source[TOKEN_SOURCE_PREFIX.len()..].to_string();
+                if !scopes.contains(&scope) {
+                    token_colors.push(json!({ "scope": scope, "settings": { "foreground": hex_string(&color) } }));
+                    scopes.push(scope);
+                }
+            } else if !colors.contains_key(source) {
+                colors.insert(source.clone(), Value::String(hex_string(&color)));
+            }
+        }
+    }
+    let unfilled =
+        VSCODE_WORKBENCH_KEYS.iter().filter(|key| !colors.contains_key(**key)).map(|key| key.to_string()).collect();
+
+    let kind = if is_dark_color(&document.colors.bg_color) { "dark" } else { "light" };
+    let json = json!({
+        "$schema": "vscode://schemas/color-theme",
+        "name": document.name,
+        "type": kind,
+        "colors": colors,
+        "tokenColors": token_colors,
+    });
+    VscodeExport { json, unfilled }
+}
+
+/// The file name for a VS Code export of a theme. Eg: "My theme" becomes "my-theme-color-theme.json"
+pub fn vscode_file_name(document: &ThemeDocument) -> String {
+    format!("{}{}", document.slug(), VSCODE_THEME_SUFFIX)
+}
+
+/// A rough light/dark test using relative luminance without gamma correction
+fn is_dark_color(color: &Color) -> bool {
+    0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b <= 0.5
+}
+
+/// List the VS Code color theme files in a directory, sorted by path. A missing directory is not an error.
+pub fn vscode_theme_files<P: AsRef<Path>>(dir: P) -> io::Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files: Vec<PathBuf> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_theme = path.file_name().map_or(false, |name| name.to_string_lossy().ends_with(VSCODE_THEME_SUFFIX));
+        if path.is_file() && is_theme {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -19,7 +19,15 @@ pub struct NodeLayout {
     pub children: Vec<NodeLayout>,
 }
 
-impl NodeLayout {}
+impl NodeLayout {
+    /// Find the layout of a node in this layout or any of its descendants
+    pub fn find(&self, id: Node) -> Option<&NodeLayout> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(id))
+    }
+}
 
 pub struct LayoutBuilder {
     pub root: Node,